@@ -1,17 +1,94 @@
 //! Simple program to run the marked library. By default the original text,
 //! AST and HTML will all be written to the console. Various flags allow
 //! turning bits off if desired.
+//!
+//! The `fmt` subcommand rewrites files as normalized mark source, with
+//! `--check` it only lists the files which are not formatted. The `view`
+//! subcommand shows a file in the terminal.
 
 #![deny(clippy::all, clippy::pedantic)]
 
-use clap::{App, Arg};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use mark::{AnsiRenderer, Renderer, TextRenderer};
 use std::fs;
+use std::io::{self, ErrorKind};
+use std::process;
+
+/// Formats each of the `FILES`. A file is left alone if the formatted source
+/// would not parse to the same document. Exits with an error if any file
+/// could not be formatted, or with `--check` if any file is not formatted.
+fn fmt(matches: &ArgMatches) {
+    let check = matches.is_present("check");
+    let mut failed = false;
+    for filename in matches.values_of("FILES").unwrap() {
+        let contents = match fs::read_to_string(filename) {
+            Ok(contents) => contents,
+            Err(err) => {
+                eprintln!("{}: {}", filename, err);
+                failed = true;
+                continue;
+            }
+        };
+
+        let formatted = mark::to_markup(&contents);
+        if formatted == contents {
+            continue;
+        }
+        if !mark::to_ast(&contents).same_content(&mark::to_ast(&formatted)) {
+            eprintln!(
+                "{}: formatting would change the document, skipped",
+                filename
+            );
+            failed = true;
+        } else if check {
+            println!("{}", filename);
+            failed = true;
+        } else if let Err(err) = fs::write(filename, formatted) {
+            eprintln!("{}: {}", filename, err);
+            failed = true;
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
+
+/// Writes `FILE` to the terminal styled with ANSI escapes, or as plain text
+/// with `--plain`.
+fn view(matches: &ArgMatches) {
+    let filename = matches.value_of("FILE").unwrap();
+    let contents = match fs::read_to_string(filename) {
+        Ok(contents) => contents,
+        Err(err) => {
+            eprintln!("{}: {}", filename, err);
+            process::exit(1);
+        }
+    };
+
+    let doc = mark::to_ast(&contents);
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let res = if matches.is_present("plain") {
+        TextRenderer.render_to_io(&doc, &mut out)
+    } else {
+        AnsiRenderer.render_to_io(&doc, &mut out)
+    };
+    // The output is often piped to a pager which may be closed early.
+    match res {
+        Err(err) if err.kind() != ErrorKind::BrokenPipe => {
+            eprintln!("{}: {}", filename, err);
+            process::exit(1);
+        }
+        _ => {}
+    }
+}
 
 fn main() {
     let matches = App::new("mark")
         .version("0.1")
         .author("dan sinclair <dj2@everburning.com")
         .about("mark conversion")
+        .setting(AppSettings::SubcommandsNegateReqs)
         .arg(
             Arg::with_name("o")
                 .short("o")
@@ -33,17 +110,67 @@ fn main() {
                 .takes_value(false)
                 .help("Skip printing HTML"),
         )
+        .arg(
+            Arg::with_name("t")
+                .short("t")
+                .long("typography")
+                .takes_value(false)
+                .help("Use typographic dashes, ellipses, arrows and quotes in the HTML"),
+        )
         .arg(
             Arg::with_name("INPUT")
                 .help("Input file to use")
                 .required(true)
                 .index(1),
         )
+        .subcommand(
+            SubCommand::with_name("fmt")
+                .about("Rewrite files as normalized mark source")
+                .arg(
+                    Arg::with_name("check")
+                        .long("check")
+                        .takes_value(false)
+                        .help("List the files which are not formatted, without changing them"),
+                )
+                .arg(
+                    Arg::with_name("FILES")
+                        .help("Files to format")
+                        .required(true)
+                        .multiple(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("view")
+                .about("Show a file in the terminal")
+                .arg(
+                    Arg::with_name("plain")
+                        .long("plain")
+                        .takes_value(false)
+                        .help("Write plain text without colors or styles"),
+                )
+                .arg(
+                    Arg::with_name("FILE")
+                        .help("File to show")
+                        .required(true)
+                        .index(1),
+                ),
+        )
         .get_matches();
 
+    if let Some(matches) = matches.subcommand_matches("fmt") {
+        return fmt(matches);
+    }
+    if let Some(matches) = matches.subcommand_matches("view") {
+        return view(matches);
+    }
+
     let skip_original = matches.is_present("o");
     let skip_ast = matches.is_present("a");
     let skip_html = matches.is_present("s");
+    let options = mark::Options {
+        typography: matches.is_present("t"),
+        ..mark::Options::default()
+    };
     let filename = &matches.value_of("INPUT").unwrap();
 
     let contents = fs::read_to_string(filename).expect("Something went wrong reading the file");
@@ -55,6 +182,6 @@ fn main() {
         println!("{:#?}\n", mark::to_ast(&contents));
     }
     if !skip_html {
-        println!("{}\n", mark::to_html(&contents));
+        println!("{}\n", mark::to_html_with(&contents, &options));
     }
 }
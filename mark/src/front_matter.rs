@@ -0,0 +1,202 @@
+//! Front matter at the start of a document. A YAML block starts and ends with
+//! a `---` line, the end may also be `...`, and a TOML block starts and ends
+//! with a `+++` line. The block is only front matter if it holds a map of
+//! keys, otherwise the lines are left to be parsed as a thematic break or a
+//! setext header.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The keys and values of the front matter.
+pub type Metadata = BTreeMap<String, Value>;
+
+/// A value in the front matter.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(untagged))]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    /// A string, TOML dates are also given as strings.
+    String(String),
+    List(Vec<Value>),
+    Map(Metadata),
+}
+impl Value {
+    /// Returns the value if it is a string.
+    #[must_use]
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns the values of a list.
+    #[must_use]
+    pub fn as_list(&self) -> Option<&[Value]> {
+        match self {
+            Value::List(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// Returns the strings of a list, or of a string of comma separated
+    /// values.
+    #[must_use]
+    pub fn as_strs(&self) -> Vec<&str> {
+        match self {
+            Value::String(s) => s
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .collect(),
+            Value::List(values) => values.iter().filter_map(Value::as_str).collect(),
+            _ => vec![],
+        }
+    }
+}
+
+/// The format of the front matter.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum FrontMatterFormat {
+    Yaml,
+    Toml,
+}
+impl FrontMatterFormat {
+    /// Returns the line which starts and ends front matter in the format.
+    #[must_use]
+    pub fn delimiter(self) -> &'static str {
+        match self {
+            FrontMatterFormat::Yaml => "---",
+            FrontMatterFormat::Toml => "+++",
+        }
+    }
+}
+
+/// The front matter of a document, the source between the delimiters and the
+/// metadata parsed from it.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FrontMatter {
+    pub format: FrontMatterFormat,
+    pub source: String,
+    pub metadata: Metadata,
+}
+impl FrontMatter {
+    /// Returns the value of `key`.
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.metadata.get(key)
+    }
+
+    /// Returns the `title` of the document.
+    #[must_use]
+    pub fn title(&self) -> Option<&str> {
+        self.get("title").and_then(Value::as_str)
+    }
+
+    /// Returns the `created` date of the document.
+    #[must_use]
+    pub fn created(&self) -> Option<&str> {
+        self.get("created").and_then(Value::as_str)
+    }
+
+    /// Returns the `tags` of the document, given as a list or a comma
+    /// separated string.
+    #[must_use]
+    pub fn tags(&self) -> Vec<&str> {
+        self.get("tags").map(Value::as_strs).unwrap_or_default()
+    }
+
+    /// Returns the `aliases` of the document, other names which can be used
+    /// to link to it.
+    #[must_use]
+    pub fn aliases(&self) -> Vec<&str> {
+        self.get("aliases").map(Value::as_strs).unwrap_or_default()
+    }
+}
+
+/// Parses the front matter at the start of `lines`. Returns the front matter
+/// and the number of lines it takes up, including the delimiters.
+pub(crate) fn parse(lines: &[&str]) -> Option<(FrontMatter, usize)> {
+    let format = match lines.first().map(|line| line.trim_end()) {
+        Some("---") => FrontMatterFormat::Yaml,
+        Some("+++") => FrontMatterFormat::Toml,
+        _ => return None,
+    };
+    let end = lines.iter().skip(1).position(|line| {
+        let line = line.trim_end();
+        line == format.delimiter() || (format == FrontMatterFormat::Yaml && line == "...")
+    })? + 1;
+
+    let source = lines[1..end].join("\n");
+    let metadata = match format {
+        FrontMatterFormat::Yaml => match serde_yaml::from_str(&source).ok()? {
+            serde_yaml::Value::Mapping(map) => from_yaml_map(map),
+            _ => return None,
+        },
+        FrontMatterFormat::Toml => match source.parse().ok()? {
+            toml::Value::Table(table) => from_toml_table(table),
+            _ => return None,
+        },
+    };
+    let front_matter = FrontMatter {
+        format,
+        source,
+        metadata,
+    };
+    Some((front_matter, end + 1))
+}
+
+fn from_yaml_map(map: serde_yaml::Mapping) -> Metadata {
+    map.into_iter()
+        .filter_map(|(key, value)| {
+            let key = match key {
+                serde_yaml::Value::String(key) => key,
+                serde_yaml::Value::Bool(key) => key.to_string(),
+                serde_yaml::Value::Number(key) => key.to_string(),
+                _ => return None,
+            };
+            Some((key, from_yaml(value)))
+        })
+        .collect()
+}
+
+fn from_yaml(value: serde_yaml::Value) -> Value {
+    match value {
+        serde_yaml::Value::Null => Value::Null,
+        serde_yaml::Value::Bool(b) => Value::Bool(b),
+        serde_yaml::Value::Number(num) => match num.as_i64() {
+            Some(num) => Value::Integer(num),
+            None => Value::Float(num.as_f64().unwrap_or(f64::NAN)),
+        },
+        serde_yaml::Value::String(s) => Value::String(s),
+        serde_yaml::Value::Sequence(values) => {
+            Value::List(values.into_iter().map(from_yaml).collect())
+        }
+        serde_yaml::Value::Mapping(map) => Value::Map(from_yaml_map(map)),
+    }
+}
+
+fn from_toml_table(table: toml::value::Table) -> Metadata {
+    table
+        .into_iter()
+        .map(|(key, value)| (key, from_toml(value)))
+        .collect()
+}
+
+fn from_toml(value: toml::Value) -> Value {
+    match value {
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Integer(num) => Value::Integer(num),
+        toml::Value::Float(num) => Value::Float(num),
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Datetime(date) => Value::String(date.to_string()),
+        toml::Value::Array(values) => Value::List(values.into_iter().map(from_toml).collect()),
+        toml::Value::Table(table) => Value::Map(from_toml_table(table)),
+    }
+}
@@ -0,0 +1,646 @@
+//! Rendering of documents to HTML. The output can be adjusted with the
+//! `Options` given to the `HtmlRenderer`.
+
+use crate::outline::{self, Heading, Slugs};
+use crate::render::Renderer;
+use crate::tree::{unescape_entity, walk, Alignment, Block, Doc, Marker, Span, Spanned, Task};
+use crate::typography::smarten;
+use crate::visit::Visitor;
+use std::borrow::Cow;
+use std::fmt;
+
+/// Resolves the targets of `[[note]]` links.
+pub trait Resolver {
+    /// Returns the URL for the note `target`.
+    fn href(&self, target: &str) -> String;
+    /// Returns true if the note `target` exists.
+    fn exists(&self, target: &str) -> bool;
+}
+
+/// Options used when rendering a document to HTML.
+#[derive(Clone, Copy)]
+pub struct Options<'r> {
+    /// Resolver for `[[note]]` links. Without a resolver the note target is
+    /// used as the URL.
+    pub resolver: Option<&'r dyn Resolver>,
+    /// How math blocks are rendered.
+    pub math: MathStyle,
+    /// Replace dashes, ellipses, arrows and straight quotes in the text with
+    /// their typographic forms. Code and raw HTML are never changed.
+    pub typography: bool,
+    /// Write empty elements in the XHTML style, `<hr />` rather than `<hr>`.
+    pub xhtml: bool,
+    /// The prefix of the class for code blocks with a language. The class is
+    /// the prefix followed by the language.
+    pub code_class_prefix: &'r str,
+    /// How line breaks within a paragraph are written.
+    pub soft_break: SoftBreak,
+    /// Write an `id` for each header, the slug of the header text. The ids
+    /// are always written for a document with a `[TOC]`, for its links.
+    pub header_ids: bool,
+}
+impl Default for Options<'_> {
+    fn default() -> Self {
+        Self {
+            resolver: None,
+            math: MathStyle::default(),
+            typography: false,
+            xhtml: true,
+            code_class_prefix: "language-",
+            soft_break: SoftBreak::default(),
+            header_ids: false,
+        }
+    }
+}
+
+/// How math is rendered to HTML.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum MathStyle {
+    /// The escaped source is output for a client side library, such as
+    /// `KaTeX`, to typeset.
+    #[default]
+    Source,
+    /// The math is converted to `MathML`.
+    #[cfg(feature = "mathml")]
+    MathMl,
+}
+
+/// How line breaks within a paragraph are written to HTML.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum SoftBreak {
+    /// The line break is kept as a newline.
+    #[default]
+    Newline,
+    /// The lines are joined with a space.
+    Space,
+    /// The line break is written as a `<br />`.
+    LineBreak,
+}
+
+/// Renders documents to HTML.
+#[derive(Clone, Copy, Default)]
+pub struct HtmlRenderer<'r> {
+    options: Options<'r>,
+}
+impl<'r> HtmlRenderer<'r> {
+    /// Creates a renderer which uses `options`.
+    #[must_use]
+    pub fn new(options: &Options<'r>) -> Self {
+        Self { options: *options }
+    }
+
+    /// Writes a single `block`, and its children, to `out`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to `out` fails.
+    pub fn render_block(&self, block: &Block, out: &mut dyn fmt::Write) -> fmt::Result {
+        HtmlWriter::new(out, &self.options).walk_block(block)
+    }
+
+    /// Writes the `outline` of a document, from `Doc::outline`, to `out` as
+    /// nested lists of links to the headers.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to `out` fails.
+    pub fn render_outline(&self, outline: &[Heading], out: &mut dyn fmt::Write) -> fmt::Result {
+        HtmlWriter::new(out, &self.options).write_outline(outline)
+    }
+
+    /// Renders each of the top level blocks of `doc` on its own, returning
+    /// the span of each block along with its HTML. The header ids and any
+    /// `[TOC]` are the same as when rendering the whole document, joining the
+    /// HTML of the blocks gives the HTML of the document.
+    ///
+    /// # Panics
+    ///
+    /// Panics if writing a block returns an error, writing to a string never
+    /// fails so the error is from the writer itself.
+    #[must_use]
+    pub fn render_blocks(&self, doc: &Doc) -> Vec<(Span, String)> {
+        let mut blocks = Vec::with_capacity(doc.blocks.len());
+        let mut slugs = Slugs::default();
+        let mut outline = toc_outline(doc);
+        for node in &doc.blocks {
+            let mut html = String::new();
+            let mut writer = HtmlWriter::new(&mut html, &self.options);
+            writer.slugs = slugs;
+            writer.outline = outline;
+            writer
+                .visit_node(node)
+                .expect("the HTML writer returned an error writing to a string");
+            slugs = writer.slugs;
+            outline = writer.outline;
+            blocks.push((node.span, html));
+        }
+        blocks
+    }
+}
+impl Renderer for HtmlRenderer<'_> {
+    fn render(&self, doc: &Doc, out: &mut dyn fmt::Write) -> fmt::Result {
+        let mut writer = HtmlWriter::new(out, &self.options);
+        writer.outline = toc_outline(doc);
+        writer.visit_doc(doc)
+    }
+}
+
+/// Returns the outline of `doc` if it has a `[TOC]` to write it in.
+fn toc_outline(doc: &Doc) -> Option<Vec<Heading>> {
+    let mut toc = false;
+    walk(&doc.blocks, &mut |block| toc |= *block == Block::Toc);
+    if toc {
+        Some(doc.outline())
+    } else {
+        None
+    }
+}
+
+/// Escapes `txt` for use as an HTML attribute value.
+fn escape_attr(txt: &str) -> Cow<'_, str> {
+    if !txt.contains(&['&', '"', '<', '>'][..]) {
+        return Cow::Borrowed(txt);
+    }
+
+    let mut escaped = String::with_capacity(txt.len());
+    for ch in txt.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            _ => escaped.push(ch),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// Escapes `txt` for use as HTML text.
+fn escape_text(txt: &str) -> Cow<'_, str> {
+    if !txt.contains(&['&', '<', '>'][..]) {
+        return Cow::Borrowed(txt);
+    }
+    Cow::Owned(
+        txt.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;"),
+    )
+}
+
+/// Writes the text content of `blocks`, without any markup, into `out`. The
+/// entities the parser puts in the text for escaped characters are written
+/// as the characters.
+fn plain_text(blocks: &[Spanned], out: &mut String) {
+    for node in blocks {
+        match &node.block {
+            Block::Text(txt) => match unescape_entity(txt) {
+                Some(ch) => out.push(ch),
+                None => out.push_str(txt),
+            },
+            Block::Inline(_, blocks)
+            | Block::Link(_, _, blocks)
+            | Block::NoteLink(_, blocks)
+            | Block::Image(_, _, blocks) => {
+                plain_text(blocks, out);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Returns the id suffix for the `reference` to a footnote. The first
+/// reference has no suffix.
+fn footnote_ref_suffix(reference: usize) -> String {
+    if reference > 1 {
+        format!("-{}", reference)
+    } else {
+        String::new()
+    }
+}
+
+/// Writes the HTML for the blocks it visits.
+struct HtmlWriter<'f, 'o> {
+    f: &'f mut dyn fmt::Write,
+    opts: Options<'o>,
+    /// Set while writing content which is output verbatim, such as code.
+    verbatim: bool,
+    /// The ids of the headers written so far.
+    slugs: Slugs,
+    /// The outline of the document, set if it has a `[TOC]`.
+    outline: Option<Vec<Heading>>,
+    /// The last character of the text written in the current block, used to
+    /// decide if a quote opens or closes.
+    prev: Option<char>,
+}
+impl<'f, 'o> HtmlWriter<'f, 'o> {
+    fn new(f: &'f mut dyn fmt::Write, opts: &Options<'o>) -> Self {
+        Self {
+            f,
+            opts: *opts,
+            verbatim: false,
+            slugs: Slugs::default(),
+            outline: None,
+            prev: None,
+        }
+    }
+
+    /// Writes `blocks` as content which is output verbatim. Typographic
+    /// replacements and the soft break policy are not applied.
+    fn write_verbatim<'v>(&mut self, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        let verbatim = self.verbatim;
+        self.verbatim = true;
+        let res = self.visit_blocks(blocks);
+        self.verbatim = verbatim;
+        res
+    }
+
+    /// Returns the end of an empty element, such as `<hr />`.
+    fn empty_end(&self) -> &'static str {
+        if self.opts.xhtml {
+            " />"
+        } else {
+            ">"
+        }
+    }
+
+    fn write_title(&mut self, title: Option<&str>) -> fmt::Result {
+        if let Some(title) = title {
+            write!(self.f, " title=\"{}\"", escape_attr(title))?;
+        }
+        Ok(())
+    }
+
+    /// Writes the headings of an `outline` as a list of links, with the
+    /// children of each heading in a nested list.
+    fn write_outline(&mut self, outline: &[Heading]) -> fmt::Result {
+        if outline.is_empty() {
+            return Ok(());
+        }
+        writeln!(self.f, "<ul>")?;
+        for heading in outline {
+            write!(
+                self.f,
+                "<li><a href=\"#{}\">{}</a>",
+                escape_attr(&heading.id),
+                escape_text(&heading.title)
+            )?;
+            if !heading.children.is_empty() {
+                writeln!(self.f)?;
+                self.write_outline(&heading.children)?;
+            }
+            writeln!(self.f, "</li>")?;
+        }
+        writeln!(self.f, "</ul>")
+    }
+
+    fn write_math(&mut self, src: &str, display: bool) -> fmt::Result {
+        match (self.opts.math, display) {
+            (MathStyle::Source, true) => writeln!(
+                self.f,
+                "<div class=\"math display\">{}</div>",
+                escape_text(src)
+            ),
+            (MathStyle::Source, false) => write!(
+                self.f,
+                "<span class=\"math inline\">{}</span>",
+                escape_text(src)
+            ),
+            #[cfg(feature = "mathml")]
+            (MathStyle::MathMl, true) => {
+                writeln!(self.f, "{}", crate::mathml::to_mathml(src, true))
+            }
+            #[cfg(feature = "mathml")]
+            (MathStyle::MathMl, false) => {
+                write!(self.f, "{}", crate::mathml::to_mathml(src, false))
+            }
+        }
+    }
+}
+
+impl<'v> Visitor<'v> for HtmlWriter<'_, '_> {
+    type Error = fmt::Error;
+
+    fn visit_blockquote(&mut self, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        writeln!(self.f, "<blockquote>")?;
+        self.visit_blocks(blocks)?;
+        writeln!(self.f, "</blockquote>")
+    }
+
+    fn visit_code(&mut self, lang: Option<&'v str>, lines: &'v [Spanned<'v>]) -> fmt::Result {
+        write!(self.f, "<pre><code")?;
+        if let Some(lang) = lang {
+            write!(
+                self.f,
+                " class=\"{}{}\"",
+                escape_attr(self.opts.code_class_prefix),
+                escape_attr(lang)
+            )?;
+        }
+        write!(self.f, ">")?;
+        self.write_verbatim(lines)?;
+        if !lines.is_empty() {
+            writeln!(self.f)?;
+        }
+        writeln!(self.f, "</code></pre>")
+    }
+
+    fn visit_header(&mut self, lvl: usize, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        write!(self.f, "<h{}", lvl)?;
+        if self.opts.header_ids || self.outline.is_some() {
+            let id = self.slugs.next(&outline::title(blocks));
+            write!(self.f, " id=\"{}\"", escape_attr(&id))?;
+        }
+        write!(self.f, ">")?;
+        self.visit_blocks(blocks)?;
+        writeln!(self.f, "</h{}>", lvl)
+    }
+
+    fn visit_list(&mut self, marker: Marker, start: u32, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        let (list, attr) = match marker {
+            Marker::Bullet | Marker::Dash | Marker::Plus => ("ul", ""),
+            Marker::UpperAlpha => ("ol", " type='A'"),
+            Marker::LowerAlpha => ("ol", " type='a'"),
+            Marker::UpperRoman => ("ol", " type='I'"),
+            Marker::LowerRoman => ("ol", " type='i'"),
+            Marker::Numeric => ("ol", ""),
+        };
+        let mut attr = attr.to_string();
+        if start != 1 {
+            attr = format!("{} start=\"{}\"", attr, start);
+        }
+        writeln!(self.f, "<{}{}>", list, attr)?;
+        self.visit_blocks(blocks)?;
+        writeln!(self.f, "</{}>", list)
+    }
+
+    fn visit_list_element(&mut self, task: Option<Task>, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        if let Some(task) = task {
+            let checked = if task.checked { " checked=\"\"" } else { "" };
+            writeln!(
+                self.f,
+                "<li class=\"task\"><input type=\"checkbox\" data-line=\"{}\"{}{}",
+                task.line,
+                checked,
+                self.empty_end()
+            )?;
+        } else {
+            writeln!(self.f, "<li>")?;
+        }
+        self.visit_blocks(blocks)?;
+        writeln!(self.f, "</li>")
+    }
+
+    fn visit_paragraph(&mut self, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        write!(self.f, "<p>")?;
+        self.visit_blocks(blocks)?;
+        writeln!(self.f, "</p>")
+    }
+
+    fn visit_thematic_break(&mut self) -> fmt::Result {
+        writeln!(self.f, "<hr{}", self.empty_end())
+    }
+
+    fn visit_toc(&mut self) -> fmt::Result {
+        let outline = self.outline.take().unwrap_or_default();
+        writeln!(self.f, "<nav class=\"toc\">")?;
+        let res = self.write_outline(&outline);
+        self.outline = Some(outline);
+        res?;
+        writeln!(self.f, "</nav>")
+    }
+
+    fn visit_node(&mut self, node: &'v Spanned<'v>) -> fmt::Result {
+        // Quotes at the start of a block always open.
+        if !node.block.is_inline() {
+            self.prev = None;
+        }
+        self.walk_block(&node.block)
+    }
+
+    fn visit_text(&mut self, txt: &'v str) -> fmt::Result {
+        if self.verbatim {
+            if let Some(last) = txt.chars().last() {
+                self.prev = Some(last);
+            }
+            return write!(self.f, "{}", txt);
+        }
+        match (txt, self.opts.soft_break) {
+            ("\n", SoftBreak::Space) => {
+                self.prev = Some(' ');
+                write!(self.f, " ")
+            }
+            ("\n", SoftBreak::LineBreak) => {
+                self.prev = Some('\n');
+                writeln!(self.f, "<br{}", self.empty_end())
+            }
+            _ if self.opts.typography => write!(self.f, "{}", smarten(txt, &mut self.prev)),
+            _ => write!(self.f, "{}", txt),
+        }
+    }
+
+    fn visit_inline(&mut self, el: &'v str, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        write!(self.f, "<{}>", el)?;
+        if el == "code" {
+            self.write_verbatim(blocks)?;
+        } else {
+            self.visit_blocks(blocks)?;
+        }
+        write!(self.f, "</{}>", el)
+    }
+
+    fn visit_raw_html(&mut self, lines: &'v [Spanned<'v>]) -> fmt::Result {
+        self.write_verbatim(lines)
+    }
+
+    fn visit_link(
+        &mut self,
+        href: &'v str,
+        title: Option<&'v str>,
+        blocks: &'v [Spanned<'v>],
+    ) -> fmt::Result {
+        write!(self.f, "<a href=\"{}\"", escape_attr(href))?;
+        self.write_title(title)?;
+        write!(self.f, ">")?;
+
+        // The text of an autolink is the address, which is written as it is.
+        let autolink = match blocks {
+            [Spanned {
+                block: Block::Text(txt),
+                ..
+            }] => href == txt || href.strip_prefix("mailto:") == Some(txt),
+            _ => false,
+        };
+        if autolink {
+            self.write_verbatim(blocks)?;
+        } else {
+            self.visit_blocks(blocks)?;
+        }
+        write!(self.f, "</a>")
+    }
+
+    fn visit_note_link(&mut self, target: &'v str, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        let (href, missing) = match self.opts.resolver {
+            Some(resolver) => (Cow::Owned(resolver.href(target)), !resolver.exists(target)),
+            None => (Cow::Borrowed(target), false),
+        };
+        write!(self.f, "<a href=\"{}\"", escape_attr(&href))?;
+        if missing {
+            write!(self.f, " class=\"missing\"")?;
+        }
+        write!(self.f, ">")?;
+        self.visit_blocks(blocks)?;
+        write!(self.f, "</a>")
+    }
+
+    fn visit_image(
+        &mut self,
+        src: &'v str,
+        title: Option<&'v str>,
+        blocks: &'v [Spanned<'v>],
+    ) -> fmt::Result {
+        let mut alt = String::new();
+        plain_text(blocks, &mut alt);
+        if let Some(last) = alt.chars().last() {
+            self.prev = Some(last);
+        }
+        write!(
+            self.f,
+            "<img src=\"{}\" alt=\"{}\"",
+            escape_attr(src),
+            escape_attr(&alt)
+        )?;
+        self.write_title(title)?;
+        write!(self.f, "{}", self.empty_end())
+    }
+
+    fn visit_math(&mut self, lines: &'v [Cow<'v, str>]) -> fmt::Result {
+        self.write_math(&lines.join("\n"), true)
+    }
+
+    fn visit_inline_math(&mut self, src: &'v str) -> fmt::Result {
+        if let Some(last) = src.chars().last() {
+            self.prev = Some(last);
+        }
+        self.write_math(src, false)
+    }
+
+    fn visit_table(&mut self, rows: &'v [Spanned<'v>]) -> fmt::Result {
+        writeln!(self.f, "<table>")?;
+        if let Some((head, body)) = rows.split_first() {
+            writeln!(self.f, "<thead>")?;
+            self.visit_node(head)?;
+            writeln!(self.f, "</thead>")?;
+            if !body.is_empty() {
+                writeln!(self.f, "<tbody>")?;
+                self.visit_blocks(body)?;
+                writeln!(self.f, "</tbody>")?;
+            }
+        }
+        writeln!(self.f, "</table>")
+    }
+
+    fn visit_table_row(&mut self, cells: &'v [Spanned<'v>]) -> fmt::Result {
+        writeln!(self.f, "<tr>")?;
+        self.visit_blocks(cells)?;
+        writeln!(self.f, "</tr>")
+    }
+
+    fn visit_table_cell(
+        &mut self,
+        header: bool,
+        align: Alignment,
+        blocks: &'v [Spanned<'v>],
+    ) -> fmt::Result {
+        let el = if header { "th" } else { "td" };
+        let attr = match align {
+            Alignment::None => "",
+            Alignment::Left => " align=\"left\"",
+            Alignment::Center => " align=\"center\"",
+            Alignment::Right => " align=\"right\"",
+        };
+        write!(self.f, "<{}{}>", el, attr)?;
+        self.visit_blocks(blocks)?;
+        writeln!(self.f, "</{}>", el)
+    }
+
+    fn visit_footnote_ref(&mut self, num: usize, reference: usize) -> fmt::Result {
+        write!(
+            self.f,
+            "<sup class=\"footnote-ref\"><a href=\"#fn-{}\" id=\"fnref-{}{}\">{}</a></sup>",
+            num,
+            num,
+            footnote_ref_suffix(reference),
+            num
+        )
+    }
+
+    fn visit_definition_list(&mut self, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        writeln!(self.f, "<dl>")?;
+        self.visit_blocks(blocks)?;
+        writeln!(self.f, "</dl>")
+    }
+
+    fn visit_definition_term(&mut self, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        write!(self.f, "<dt>")?;
+        self.visit_blocks(blocks)?;
+        writeln!(self.f, "</dt>")
+    }
+
+    fn visit_definition(&mut self, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        writeln!(self.f, "<dd>")?;
+        self.visit_blocks(blocks)?;
+        writeln!(self.f, "</dd>")
+    }
+
+    fn visit_footnotes(&mut self, footnotes: &'v [Spanned<'v>]) -> fmt::Result {
+        writeln!(self.f, "<section class=\"footnotes\">\n<ol>")?;
+        self.visit_blocks(footnotes)?;
+        writeln!(self.f, "</ol>\n</section>")
+    }
+
+    /// Writes the footnote `num` along with a link back to each of the `refs`
+    /// references. The back links are placed in the final paragraph of the
+    /// footnote if it ends in a paragraph.
+    fn visit_footnote(
+        &mut self,
+        num: usize,
+        refs: usize,
+        blocks: &'v [Spanned<'v>],
+    ) -> fmt::Result {
+        writeln!(self.f, "<li id=\"fn-{}\">", num)?;
+        let (last, rest) = match blocks.split_last() {
+            Some((
+                Spanned {
+                    block: Block::Paragraph(last),
+                    ..
+                },
+                rest,
+            )) => (Some(last), rest),
+            _ => (None, blocks),
+        };
+        self.visit_blocks(rest)?;
+        if let Some(last) = last {
+            write!(self.f, "<p>")?;
+            self.visit_blocks(last)?;
+        }
+        for reference in 1..=refs {
+            if last.is_some() || reference > 1 {
+                write!(self.f, " ")?;
+            }
+            write!(
+                self.f,
+                "<a href=\"#fnref-{}{}\" class=\"footnote-backref\">↩",
+                num,
+                footnote_ref_suffix(reference)
+            )?;
+            if reference > 1 {
+                write!(self.f, "<sup>{}</sup>", reference)?;
+            }
+            write!(self.f, "</a>")?;
+        }
+        if last.is_some() {
+            write!(self.f, "</p>")?;
+        }
+        writeln!(self.f, "\n</li>")
+    }
+}
@@ -5,14 +5,32 @@
 //! Specifically, things like indented code blocks are not supported and strong
 //! and emphasis are not differentiated by the number of markers.
 
+mod front_matter;
+mod html;
+mod markup;
+#[cfg(feature = "mathml")]
+mod mathml;
+mod outline;
 mod parser;
+mod render;
+mod text;
 mod tree;
+mod typography;
+mod visit;
 
 #[macro_use]
 extern crate lazy_static;
 
 use crate::parser::Parser;
-use crate::tree::Doc;
+
+pub use crate::front_matter::{FrontMatter, FrontMatterFormat, Metadata, Value};
+pub use crate::html::{HtmlRenderer, MathStyle, Options, Resolver, SoftBreak};
+pub use crate::markup::MarkupRenderer;
+pub use crate::outline::Heading;
+pub use crate::render::Renderer;
+pub use crate::text::{AnsiRenderer, TextRenderer};
+pub use crate::tree::{Alignment, Block, Doc, Marker, Position, Span, Spanned, Task};
+pub use crate::visit::{Visitor, VisitorMut};
 
 #[must_use]
 pub fn to_ast(buf: &'_ str) -> Doc<'_> {
@@ -26,3 +44,38 @@ pub fn to_html(buf: &str) -> String {
     let doc = p.parse();
     doc.to_string()
 }
+
+/// Converts `buf` to HTML using the provided rendering `options`.
+#[must_use]
+pub fn to_html_with(buf: &str, options: &Options) -> String {
+    let mut p = Parser::new(buf);
+    p.parse().to_html(options)
+}
+
+/// Converts `buf` to normalized mark source, see `MarkupRenderer`.
+#[must_use]
+pub fn to_markup(buf: &str) -> String {
+    let mut p = Parser::new(buf);
+    p.parse().to_markup()
+}
+
+/// Converts `buf` to plain text, see `TextRenderer`.
+#[must_use]
+pub fn to_text(buf: &str) -> String {
+    let mut p = Parser::new(buf);
+    p.parse().to_text()
+}
+
+/// Returns the outline of the headers in `buf`, see `Doc::outline`.
+#[must_use]
+pub fn outline(buf: &str) -> Vec<Heading> {
+    let mut p = Parser::new(buf);
+    p.parse().outline()
+}
+
+/// Returns every task list item in `buf` along with the line it is on.
+#[must_use]
+pub fn tasks(buf: &str) -> Vec<Task> {
+    let mut p = Parser::new(buf);
+    p.parse().tasks()
+}
@@ -0,0 +1,769 @@
+//! Rendering of documents back to mark source. The `MarkupRenderer` writes a
+//! normalized form of the source which parses to the same document. Headers
+//! are written in the `#` style unless they span several lines, code is fenced
+//! with backticks, list items are numbered in order, adjacent bullet lists
+//! alternate their bullets so they stay separate, reference links are
+//! written inline and footnotes are numbered and moved to the end of the
+//! document. Text is only escaped where it would otherwise be read as markup.
+//! Front matter is written back as it was in the source.
+
+use crate::render::Renderer;
+use crate::tree::{unescape_entity, Alignment, Block, Doc, Marker, Spanned, Task};
+use crate::visit::Visitor;
+use regex::Regex;
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::fmt::{self, Write};
+use std::mem;
+
+/// Renders a document as normalized mark source.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MarkupRenderer;
+
+impl Renderer for MarkupRenderer {
+    fn render(&self, doc: &Doc, out: &mut dyn fmt::Write) -> fmt::Result {
+        if let Some(front_matter) = &doc.front_matter {
+            let delimiter = front_matter.format.delimiter();
+            writeln!(out, "{}", delimiter)?;
+            if !front_matter.source.is_empty() {
+                writeln!(out, "{}", front_matter.source)?;
+            }
+            writeln!(out, "{}", delimiter)?;
+            if !doc.blocks.is_empty() {
+                writeln!(out)?;
+            }
+        }
+
+        let mut writer = MarkupWriter::default();
+        writer.write_blocks(&doc.blocks)?;
+        let mut body = writer.out;
+        // A leading `---` or `+++` line could be read as the start of front
+        // matter.
+        if doc.front_matter.is_none() {
+            if body.starts_with("---\n") {
+                body.replace_range(..3, "***");
+            } else if body.starts_with("+++\n") {
+                body.insert(0, '\\');
+            }
+        }
+        out.write_str(&body)
+    }
+}
+
+/// Returns the bullet character of a bullet list `marker`.
+fn bullet(marker: Marker) -> Option<char> {
+    match marker {
+        Marker::Bullet => Some('*'),
+        Marker::Dash => Some('-'),
+        Marker::Plus => Some('+'),
+        _ => None,
+    }
+}
+
+/// Returns the marker for item `idx` of a list. Bullet items use `close` as
+/// their bullet. Numbered and lettered items count up from `start` and end
+/// with `close`.
+fn list_marker(marker: Marker, start: u32, idx: usize, close: char) -> String {
+    let num = start.saturating_add(u32::try_from(idx).unwrap_or(u32::MAX));
+    match marker {
+        Marker::Bullet | Marker::Dash | Marker::Plus => close.to_string(),
+        Marker::Numeric => format!("{}{}", num, close),
+        Marker::LowerAlpha => format!("{}{}", alpha_marker(b'a', start, num), close),
+        Marker::UpperAlpha => format!("{}{}", alpha_marker(b'A', start, num), close),
+        // Only a single letter is a marker, the parser numbers the items.
+        Marker::LowerRoman => format!("i{}", close),
+        Marker::UpperRoman => format!("I{}", close),
+    }
+}
+
+/// Returns the letter for item `num` of a lettered list. An item past `z`, or
+/// which would be read as a roman numeral list, repeats the `start` letter.
+fn alpha_marker(first: u8, start: u32, num: u32) -> char {
+    let letter = |n: u32| {
+        u8::try_from(n.saturating_sub(1))
+            .ok()
+            .filter(|&n| n < 26)
+            .map(|n| char::from(first + n))
+    };
+    match letter(num) {
+        Some(ch) if !ch.eq_ignore_ascii_case(&'i') => ch,
+        _ => letter(start).unwrap_or_else(|| char::from(first)),
+    }
+}
+
+/// Returns the inline marker for the element `el`.
+fn inline_marker(el: &str) -> &'static str {
+    match el {
+        "em" => "_",
+        "strong" => "*",
+        "code" => "`",
+        "sup" => "^",
+        "sub" => "~",
+        "del" => "~~",
+        "mark" => "==",
+        _ => "",
+    }
+}
+
+/// Returns the code fence for `src`, the fence is longer than any line of
+/// `src` which would close it. Backticks are used unless the `lang` contains a
+/// backtick.
+fn code_fence(src: &str, lang: Option<&str>) -> String {
+    let ch = if matches!(lang, Some(l) if l.contains('`')) {
+        '~'
+    } else {
+        '`'
+    };
+    let longest = src
+        .lines()
+        .map(str::trim)
+        .filter(|l| l.chars().all(|c| c == ch))
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    ch.to_string().repeat(longest.max(2) + 1)
+}
+
+/// Escapes the backslashes in `txt` which would otherwise escape the
+/// following punctuation, or the character after the end of `txt`.
+fn escape_backslashes(txt: &str) -> Cow<'_, str> {
+    if !txt.contains('\\') {
+        return Cow::Borrowed(txt);
+    }
+    let mut out = String::with_capacity(txt.len() + 1);
+    let mut chars = txt.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch == '\\' && !matches!(chars.peek(), Some(&c) if !c.is_ascii_punctuation()) {
+            out.push('\\');
+        }
+        out.push(ch);
+    }
+    Cow::Owned(out)
+}
+
+/// Returns the link destination `dest` as it is written in a link. A
+/// destination with spaces or unbalanced brackets is wrapped in `<>`.
+fn link_dest(dest: &str) -> String {
+    let mut depth = 0;
+    let plain = dest.chars().all(|ch| match ch {
+        '(' => {
+            depth += 1;
+            depth == 1
+        }
+        ')' => {
+            depth -= 1;
+            depth == 0
+        }
+        '<' | '>' => false,
+        _ => !ch.is_whitespace(),
+    }) && depth == 0;
+
+    let dest = escape_backslashes(dest);
+    if plain {
+        dest.into_owned()
+    } else {
+        format!("<{}>", dest)
+    }
+}
+
+/// Returns the `title` of a link as it is written in a link.
+fn link_title(title: Option<&str>) -> String {
+    title.map_or_else(String::new, |title| {
+        format!(" \"{}\"", escape_backslashes(title).replace('"', "\\\""))
+    })
+}
+
+/// Returns the source for a link which can be written as an autolink, the
+/// link text must be the destination, or the address of an email link.
+fn autolink<'v>(href: &str, blocks: &'v [Spanned<'v>]) -> Option<&'v str> {
+    lazy_static! {
+        static ref URI_RE: Regex = Regex::new(r"^[A-Za-z][A-Za-z0-9+.\-]{1,31}:[^\s<>]*$").unwrap();
+        static ref EMAIL_RE: Regex = Regex::new(
+            r"^[a-zA-Z0-9.!#$%&'*+/=?^_`{|}~\-]+@[a-zA-Z0-9](?:[a-zA-Z0-9\-]{0,61}[a-zA-Z0-9])?(?:\.[a-zA-Z0-9](?:[a-zA-Z0-9\-]{0,61}[a-zA-Z0-9])?)*$"
+        )
+        .unwrap();
+    }
+
+    let txt = match blocks {
+        [Spanned {
+            block: Block::Text(txt),
+            ..
+        }] => txt.as_ref(),
+        _ => return None,
+    };
+    let is_uri = href == txt && URI_RE.is_match(txt);
+    let is_email = href.strip_prefix("mailto:") == Some(txt) && EMAIL_RE.is_match(txt);
+    if is_uri || is_email {
+        Some(txt)
+    } else {
+        None
+    }
+}
+
+/// Escapes the start of a `line` of inline text which would otherwise be read
+/// as the start of a block, such as a list item or a thematic break.
+fn escape_block_start(line: &str) -> Cow<'_, str> {
+    lazy_static! {
+        static ref ORDERED_RE: Regex =
+            Regex::new(r"^(?:[0-9]{1,9}|[a-zA-Z])[.)](?:\s|$)").unwrap();
+        static ref BLOCK_RE: Regex = Regex::new(
+            r"^(?:#{1,6}(?:\s|$)|[*+\-](?:\s|$)|:\s|`{3,}|~{3,}|(?:\s*\*){3,}\s*$|(?:\s*-){3,}\s*$|(?:\s*_){3,}\s*$|={3,}\s*$)"
+        )
+        .unwrap();
+        static ref TABLE_DELIM_RE: Regex =
+            Regex::new(r"^\|?\s*:?-+:?\s*(?:\|\s*:?-+:?\s*)*\|?\s*$").unwrap();
+    }
+
+    if ORDERED_RE.is_match(line) {
+        let close = line.find(&['.', ')'][..]).unwrap_or(0);
+        Cow::Owned(format!("{}\\{}", &line[..close], &line[close..]))
+    } else if BLOCK_RE.is_match(line) || (line.contains('|') && TABLE_DELIM_RE.is_match(line)) {
+        Cow::Owned(format!("\\{}", line))
+    } else {
+        Cow::Borrowed(line)
+    }
+}
+
+/// Writes the mark source for the blocks it visits. Container blocks render
+/// their content on its own and then prefix each line, so a list item or
+/// blockquote can hold any other block.
+#[derive(Default)]
+#[allow(clippy::struct_excessive_bools)]
+struct MarkupWriter<'v> {
+    out: String,
+    /// The lines of `out`, counted from 0, which are math or HTML source. The
+    /// parser keeps the indent of these lines inside a list item, so the item
+    /// must not indent them again.
+    keep_indent: Vec<usize>,
+    /// The inline elements and links around the text being written, the
+    /// innermost is last.
+    inlines: Vec<&'v str>,
+    /// The marker for the next list element.
+    item: String,
+    /// The close of the next numbered or lettered list, `.` or `)`, or the
+    /// bullet of the next bullet list. Adjacent lists of the same kind
+    /// alternate so they aren't joined.
+    close: char,
+    /// Set while writing an inline which the parser left open at the end of a
+    /// line, the inline is written without its closing marker.
+    unclosed: bool,
+    /// Set directly after an inline marker which opens an inline.
+    after_open: bool,
+    /// Set while writing the content of a code span.
+    code: bool,
+    /// Set while writing a table, where a `|` would split the cell.
+    table: bool,
+}
+impl<'v> MarkupWriter<'v> {
+    /// Writes each of the `blocks`, separated by blank lines.
+    fn write_blocks(&mut self, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        // The marker of the previous list and the close it was written with.
+        let mut prev = None;
+        for (i, node) in blocks.iter().enumerate() {
+            if i > 0 {
+                writeln!(self.out)?;
+            }
+            if let Block::List(marker, ..) = node.block {
+                self.close = match (bullet(marker), prev) {
+                    // A bullet list after one with the same bullet takes a
+                    // bullet which neither of its neighbours uses.
+                    (Some(ch), Some((_, close))) if close == ch => {
+                        let next = match blocks.get(i + 1).map(|node| &node.block) {
+                            Some(Block::List(marker, ..)) => bullet(*marker),
+                            _ => None,
+                        };
+                        ['-', '*', '+']
+                            .iter()
+                            .copied()
+                            .find(|&c| c != ch && Some(c) != next)
+                            .unwrap_or(ch)
+                    }
+                    (Some(ch), _) => ch,
+                    (None, Some((prev, '.'))) if prev == marker => ')',
+                    (None, _) => '.',
+                };
+                prev = Some((marker, self.close));
+            } else {
+                prev = None;
+            }
+            self.visit_node(node)?;
+        }
+        Ok(())
+    }
+
+    /// Renders the `blocks` of a container on their own. Returns the source
+    /// along with the lines of it which keep their indent.
+    fn render_blocks(
+        &mut self,
+        blocks: &'v [Spanned<'v>],
+    ) -> Result<(String, Vec<usize>), fmt::Error> {
+        let out = mem::take(&mut self.out);
+        let keep_indent = mem::take(&mut self.keep_indent);
+        let res = self.write_blocks(blocks);
+        let body = mem::replace(&mut self.out, out);
+        let body_keep_indent = mem::replace(&mut self.keep_indent, keep_indent);
+        res.map(|()| (body, body_keep_indent))
+    }
+
+    /// Writes a `line` of math or HTML source which keeps its indent.
+    fn write_kept_line(&mut self, line: &str) -> fmt::Result {
+        let num = self.out.matches('\n').count();
+        self.keep_indent.push(num);
+        writeln!(self.out, "{}", line)
+    }
+
+    /// Renders the inline `blocks` of a block on their own. An `unclosed`
+    /// last inline is left open.
+    fn render_inlines(
+        &mut self,
+        blocks: &'v [Spanned<'v>],
+        unclosed: bool,
+    ) -> Result<String, fmt::Error> {
+        let out = mem::take(&mut self.out);
+        let res = self.write_inlines(blocks, unclosed);
+        let body = mem::replace(&mut self.out, out);
+        res.map(|()| body)
+    }
+
+    /// Writes the inline `blocks`. An `unclosed` last inline is left open.
+    fn write_inlines(&mut self, blocks: &'v [Spanned<'v>], unclosed: bool) -> fmt::Result {
+        for (i, node) in blocks.iter().enumerate() {
+            self.unclosed =
+                unclosed && i + 1 == blocks.len() && matches!(node.block, Block::Inline(..));
+            self.visit_node(node)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the lines of `body` with `first` before the first line and
+    /// `rest` before the others. A `list` item doesn't indent the lines of
+    /// `body` listed in `keep_indent`.
+    fn write_prefixed(
+        &mut self,
+        body: &str,
+        keep_indent: &[usize],
+        first: &str,
+        rest: &str,
+        list: bool,
+    ) -> fmt::Result {
+        if body.is_empty() {
+            return writeln!(self.out, "{}", first.trim_end());
+        }
+        for (i, line) in body.lines().enumerate() {
+            let prefix = if i == 0 { first } else { rest };
+            if i > 0 && list && keep_indent.contains(&i) {
+                self.write_kept_line(line)?;
+            } else if line.is_empty() {
+                writeln!(self.out, "{}", prefix.trim_end())?;
+            } else {
+                writeln!(self.out, "{}{}", prefix, line)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the inline `blocks` of a paragraph or term, escaping any line
+    /// which would start a block.
+    fn write_paragraph(&mut self, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        // The parser ends the inlines of a paragraph at a line which leaves an
+        // inline open, the lines after it are nested in a paragraph or header.
+        let nested = blocks
+            .iter()
+            .position(|node| matches!(node.block, Block::Paragraph(_) | Block::Header(..)));
+        let (inlines, rest) = blocks.split_at(nested.unwrap_or(blocks.len()));
+        let txt = self.render_inlines(inlines, nested.is_some())?;
+        for line in txt.split('\n') {
+            writeln!(self.out, "{}", escape_block_start(line))?;
+        }
+        match rest.split_first() {
+            Some((node, rest)) => {
+                match &node.block {
+                    Block::Header(lvl, blocks) => {
+                        self.write_paragraph(blocks)?;
+                        writeln!(self.out, "{}", if *lvl == 1 { "===" } else { "---" })?;
+                    }
+                    Block::Paragraph(blocks) => self.write_paragraph(blocks)?,
+                    _ => unreachable!(),
+                }
+                if rest.is_empty() {
+                    Ok(())
+                } else {
+                    self.write_paragraph(rest)
+                }
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Determines if the marker `ch` in text would be read as markup.
+    fn is_markup(&self, ch: char, left: Option<char>, right: Option<char>) -> bool {
+        let el = match ch {
+            '_' => "em",
+            '*' => "strong",
+            _ => "code",
+        };
+        let opens = !matches!(left, Some(c) if !c.is_whitespace())
+            && !matches!(right, Some(c) if c.is_whitespace() || c == ch);
+        let closes = self.inlines.last() == Some(&el)
+            && matches!(left, Some(c) if !c.is_whitespace() && c != ch);
+        opens || closes
+    }
+
+    /// Writes the text `txt` escaping any characters which would be read as
+    /// markup. Only backticks and backslashes are read inside of a code span.
+    fn write_escaped(&mut self, txt: &str) {
+        let chars: Vec<char> = txt.chars().collect();
+        for (i, &ch) in chars.iter().enumerate() {
+            let left = if i > 0 {
+                Some(chars[i - 1])
+            } else {
+                self.out.chars().last()
+            };
+            let right = chars.get(i + 1).copied();
+            let after_open = i == 0 && mem::take(&mut self.after_open);
+            let escape = match ch {
+                '\\' => !matches!(right, Some(c) if !c.is_ascii_punctuation()),
+                '`' if self.code => true,
+                '|' => self.table,
+                _ if self.code => false,
+                '[' | ']' | '^' | '~' => true,
+                '$' => !matches!(right, Some(c) if c.is_whitespace()),
+                '=' => !matches!(right, Some(c) if c != '='),
+                '_' | '*' | '`' => after_open || self.is_markup(ch, left, right),
+                _ => false,
+            };
+            if escape {
+                self.out.push('\\');
+            }
+            self.out.push(ch);
+        }
+    }
+}
+
+impl<'v> Visitor<'v> for MarkupWriter<'v> {
+    type Error = fmt::Error;
+
+    fn visit_blockquote(&mut self, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        let (body, keep_indent) = self.render_blocks(blocks)?;
+        self.write_prefixed(&body, &keep_indent, "> ", "> ", false)
+    }
+
+    fn visit_code(&mut self, lang: Option<&'v str>, lines: &'v [Spanned<'v>]) -> fmt::Result {
+        // The parser replaces `<` and `>` in code, write them back as is.
+        let mut src = String::new();
+        for node in lines {
+            if let Block::Text(txt) = &node.block {
+                match unescape_entity(txt) {
+                    Some(ch) => src.push(ch),
+                    None => src.push_str(txt),
+                }
+            }
+        }
+        let fence = code_fence(&src, lang);
+        writeln!(self.out, "{}{}", fence, lang.unwrap_or(""))?;
+        if !src.is_empty() {
+            writeln!(self.out, "{}", src)?;
+        }
+        writeln!(self.out, "{}", fence)
+    }
+
+    fn visit_header(&mut self, lvl: usize, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        let txt = self.render_inlines(blocks, false)?;
+
+        // A header which spans lines can only be written as a setext header.
+        if txt.contains('\n') && lvl <= 2 {
+            for line in txt.split('\n') {
+                writeln!(self.out, "{}", escape_block_start(line))?;
+            }
+            return writeln!(self.out, "{}", if lvl == 1 { "===" } else { "---" });
+        }
+
+        write!(self.out, "{}", "#".repeat(lvl))?;
+        if txt.is_empty() {
+            return writeln!(self.out);
+        }
+        // Leading `#`s and trailing `#`s after a space are part of the header
+        // markup.
+        let mut txt = txt.replace('\n', " ");
+        if txt.starts_with('#') {
+            txt.insert(0, '\\');
+        }
+        let hashes = txt.trim_end_matches('#').len();
+        if hashes < txt.len() && txt[..hashes].ends_with(char::is_whitespace) {
+            txt.insert(hashes, '\\');
+        }
+        writeln!(self.out, " {}", txt)
+    }
+
+    fn visit_list(&mut self, marker: Marker, start: u32, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        let close = self.close;
+        for (i, node) in blocks.iter().enumerate() {
+            self.item = list_marker(marker, start, i, close);
+            self.visit_node(node)?;
+        }
+        Ok(())
+    }
+
+    fn visit_list_element(&mut self, task: Option<Task>, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        let marker = mem::take(&mut self.item);
+        let rest = " ".repeat(marker.len() + 1);
+        let first = match task {
+            Some(Task { checked: true, .. }) => format!("{} [x] ", marker),
+            Some(Task { checked: false, .. }) => format!("{} [ ] ", marker),
+            None => format!("{} ", marker),
+        };
+
+        let (mut body, keep_indent) = self.render_blocks(blocks)?;
+        if body.is_empty() && task.is_some() {
+            // The space after the task marker is needed for an empty task.
+            return writeln!(self.out, "{}", first);
+        }
+        // A `- ---` line is a thematic break rather than a list item.
+        if marker == "-" && body.starts_with("---\n") {
+            body.replace_range(..3, "***");
+        }
+        // The parser keeps the indent of code which starts on the line of the
+        // marker, so the code starts on the next line.
+        if task.is_none()
+            && matches!(
+                blocks.first(),
+                Some(Spanned {
+                    block: Block::Code(..),
+                    ..
+                })
+            )
+        {
+            writeln!(self.out, "{}", marker)?;
+            return self.write_prefixed(&body, &keep_indent, &rest, &rest, true);
+        }
+        self.write_prefixed(&body, &keep_indent, &first, &rest, true)
+    }
+
+    fn visit_paragraph(&mut self, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        self.write_paragraph(blocks)
+    }
+
+    fn visit_thematic_break(&mut self) -> fmt::Result {
+        writeln!(self.out, "---")
+    }
+
+    fn visit_toc(&mut self) -> fmt::Result {
+        writeln!(self.out, "[TOC]")
+    }
+
+    fn visit_text(&mut self, txt: &'v str) -> fmt::Result {
+        if txt.is_empty() {
+            return Ok(());
+        }
+        // The parser replaces escapes with entities in the text.
+        if let Some(ch) = unescape_entity(txt) {
+            self.out.push('\\');
+            self.out.push(ch);
+        } else if txt == "\n" {
+            self.out.push('\n');
+        } else {
+            self.write_escaped(txt);
+        }
+        self.after_open = false;
+        Ok(())
+    }
+
+    fn visit_inline(&mut self, el: &'v str, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        let marker = inline_marker(el);
+        let unclosed = mem::take(&mut self.unclosed);
+        self.out.push_str(marker);
+        self.after_open = true;
+        let code = self.code;
+        self.code = code || el == "code";
+        self.inlines.push(el);
+        let res = self.write_inlines(blocks, unclosed);
+        self.inlines.pop();
+        self.code = code;
+        self.after_open = false;
+        res?;
+
+        // An inline which couldn't be closed runs to the end of the block or
+        // line, it is left unclosed.
+        let closes = !unclosed
+            && self
+                .out
+                .ends_with(|c: char| !c.is_whitespace() && !marker.starts_with(c));
+        if closes {
+            self.out.push_str(marker);
+        }
+        Ok(())
+    }
+
+    fn visit_raw_html(&mut self, lines: &'v [Spanned<'v>]) -> fmt::Result {
+        let src: String = lines
+            .iter()
+            .map(|node| match &node.block {
+                Block::Text(txt) => txt.as_ref(),
+                _ => "",
+            })
+            .collect();
+        for line in src.lines() {
+            self.write_kept_line(line)?;
+        }
+        Ok(())
+    }
+
+    fn visit_link(
+        &mut self,
+        href: &'v str,
+        title: Option<&'v str>,
+        blocks: &'v [Spanned<'v>],
+    ) -> fmt::Result {
+        if title.is_none() {
+            if let Some(src) = autolink(href, blocks) {
+                return write!(self.out, "<{}>", src);
+            }
+        }
+        // A `!` before the link would make it an image.
+        if self.out.ends_with('!') {
+            self.out.insert(self.out.len() - 1, '\\');
+        }
+        self.out.push('[');
+        self.inlines.push("a");
+        let res = self.visit_blocks(blocks);
+        self.inlines.pop();
+        res?;
+        write!(self.out, "]({}{})", link_dest(href), link_title(title))
+    }
+
+    fn visit_note_link(&mut self, target: &'v str, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        let label = match blocks {
+            [Spanned {
+                block: Block::Text(txt),
+                ..
+            }] if txt == target => None,
+            _ => Some(blocks),
+        };
+        write!(self.out, "[[{}", target)?;
+        if let Some(label) = label {
+            self.out.push('|');
+            self.inlines.push("a");
+            let res = self.visit_blocks(label);
+            self.inlines.pop();
+            res?;
+        }
+        write!(self.out, "]]")
+    }
+
+    fn visit_image(
+        &mut self,
+        src: &'v str,
+        title: Option<&'v str>,
+        blocks: &'v [Spanned<'v>],
+    ) -> fmt::Result {
+        self.out.push_str("![");
+        self.inlines.push("img");
+        let res = self.visit_blocks(blocks);
+        self.inlines.pop();
+        res?;
+        write!(self.out, "]({}{})", link_dest(src), link_title(title))
+    }
+
+    fn visit_math(&mut self, lines: &'v [Cow<'v, str>]) -> fmt::Result {
+        writeln!(self.out, "$$")?;
+        for line in lines {
+            self.write_kept_line(line)?;
+        }
+        writeln!(self.out, "$$")
+    }
+
+    fn visit_inline_math(&mut self, src: &'v str) -> fmt::Result {
+        write!(self.out, "${}$", src)
+    }
+
+    fn visit_table(&mut self, rows: &'v [Spanned<'v>]) -> fmt::Result {
+        let table = mem::replace(&mut self.table, true);
+        let mut cells: Vec<Vec<(Alignment, String)>> = vec![];
+        for row in rows {
+            let mut txts = vec![];
+            for cell in row.block.children() {
+                if let Block::TableCell(_, align, blocks) = &cell.block {
+                    txts.push((*align, self.render_inlines(blocks, false)?));
+                }
+            }
+            cells.push(txts);
+        }
+        self.table = table;
+
+        let columns = cells.first().map_or(0, Vec::len);
+        let widths: Vec<usize> = (0..columns)
+            .map(|col| {
+                cells
+                    .iter()
+                    .filter_map(|row| row.get(col))
+                    .map(|(_, txt)| txt.chars().count())
+                    .fold(3, usize::max)
+            })
+            .collect();
+        let delims: Vec<(Alignment, String)> = cells
+            .first()
+            .map(|row| {
+                row.iter()
+                    .zip(&widths)
+                    .map(|((align, _), &width)| {
+                        let delim = match align {
+                            Alignment::None => "-".repeat(width),
+                            Alignment::Left => format!(":{}", "-".repeat(width - 1)),
+                            Alignment::Right => format!("{}:", "-".repeat(width - 1)),
+                            Alignment::Center => format!(":{}:", "-".repeat(width - 2)),
+                        };
+                        (*align, delim)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        cells.insert(1.min(cells.len()), delims);
+
+        for row in &cells {
+            write!(self.out, "|")?;
+            for ((_, txt), width) in row.iter().zip(&widths) {
+                write!(self.out, " {:1$} |", txt, width)?;
+            }
+            writeln!(self.out)?;
+        }
+        Ok(())
+    }
+
+    fn visit_footnote_ref(&mut self, num: usize, _reference: usize) -> fmt::Result {
+        write!(self.out, "[^{}]", num)
+    }
+
+    fn visit_definition_list(&mut self, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        for (i, node) in blocks.iter().enumerate() {
+            if i > 0 && matches!(node.block, Block::DefinitionTerm(_)) {
+                writeln!(self.out)?;
+            }
+            self.visit_node(node)?;
+        }
+        Ok(())
+    }
+
+    fn visit_definition_term(&mut self, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        self.write_paragraph(blocks)
+    }
+
+    fn visit_definition(&mut self, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        let (body, keep_indent) = self.render_blocks(blocks)?;
+        self.write_prefixed(&body, &keep_indent, ": ", "  ", false)
+    }
+
+    fn visit_footnotes(&mut self, footnotes: &'v [Spanned<'v>]) -> fmt::Result {
+        self.write_blocks(footnotes)
+    }
+
+    fn visit_footnote(
+        &mut self,
+        num: usize,
+        _refs: usize,
+        blocks: &'v [Spanned<'v>],
+    ) -> fmt::Result {
+        let (body, keep_indent) = self.render_blocks(blocks)?;
+        let first = format!("[^{}]: ", num);
+        self.write_prefixed(&body, &keep_indent, &first, "    ", false)
+    }
+}
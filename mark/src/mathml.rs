@@ -0,0 +1,339 @@
+//! Converts the LaTeX math source of math blocks into `MathML`. Only a subset of
+//! LaTeX is supported: identifiers, numbers and operators, groups, sub and
+//! superscripts, fractions, roots, text, greek letters, common symbols and
+//! `\left`/`\right` delimiters. Anything else is output as an `<merror>`.
+
+/// Converts the math `src` to a `MathML` `<math>` element. If `display` is set
+/// the math is rendered as a block, otherwise inline.
+pub fn to_mathml(src: &str, display: bool) -> String {
+    let mut p = TexParser { src, pos: 0 };
+    format!(
+        "<math xmlns=\"http://www.w3.org/1998/Math/MathML\" display=\"{}\"><mrow>{}</mrow></math>",
+        if display { "block" } else { "inline" },
+        p.parse_row(None)
+    )
+}
+
+fn escape(txt: &str) -> String {
+    txt.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Returns the character for a greek letter command.
+fn greek(cmd: &str) -> Option<char> {
+    let ch = match cmd {
+        "alpha" => 'α',
+        "beta" => 'β',
+        "gamma" => 'γ',
+        "delta" => 'δ',
+        "epsilon" | "varepsilon" => 'ε',
+        "zeta" => 'ζ',
+        "eta" => 'η',
+        "theta" | "vartheta" => 'θ',
+        "iota" => 'ι',
+        "kappa" => 'κ',
+        "lambda" => 'λ',
+        "mu" => 'μ',
+        "nu" => 'ν',
+        "xi" => 'ξ',
+        "pi" => 'π',
+        "rho" => 'ρ',
+        "sigma" => 'σ',
+        "tau" => 'τ',
+        "upsilon" => 'υ',
+        "phi" | "varphi" => 'φ',
+        "chi" => 'χ',
+        "psi" => 'ψ',
+        "omega" => 'ω',
+        "Gamma" => 'Γ',
+        "Delta" => 'Δ',
+        "Theta" => 'Θ',
+        "Lambda" => 'Λ',
+        "Xi" => 'Ξ',
+        "Pi" => 'Π',
+        "Sigma" => 'Σ',
+        "Phi" => 'Φ',
+        "Psi" => 'Ψ',
+        "Omega" => 'Ω',
+        _ => return None,
+    };
+    Some(ch)
+}
+
+/// Returns the character for a symbol command which is an operator.
+fn operator(cmd: &str) -> Option<char> {
+    let ch = match cmd {
+        "sum" => '∑',
+        "prod" => '∏',
+        "int" => '∫',
+        "oint" => '∮',
+        "pm" => '±',
+        "mp" => '∓',
+        "times" => '×',
+        "cdot" => '⋅',
+        "div" => '÷',
+        "leq" | "le" => '≤',
+        "geq" | "ge" => '≥',
+        "neq" | "ne" => '≠',
+        "approx" => '≈',
+        "equiv" => '≡',
+        "sim" => '∼',
+        "propto" => '∝',
+        "to" | "rightarrow" => '→',
+        "leftarrow" => '←',
+        "Rightarrow" => '⇒',
+        "Leftarrow" => '⇐',
+        "leftrightarrow" => '↔',
+        "Leftrightarrow" => '⇔',
+        "in" => '∈',
+        "notin" => '∉',
+        "subset" => '⊂',
+        "subseteq" => '⊆',
+        "cup" => '∪',
+        "cap" => '∩',
+        "forall" => '∀',
+        "exists" => '∃',
+        "neg" => '¬',
+        "wedge" | "land" => '∧',
+        "vee" | "lor" => '∨',
+        "partial" => '∂',
+        "nabla" => '∇',
+        "ldots" | "dots" => '…',
+        "cdots" => '⋯',
+        "langle" => '⟨',
+        "rangle" => '⟩',
+        "{" => '{',
+        "}" => '}',
+        "|" => '‖',
+        _ => return None,
+    };
+    Some(ch)
+}
+
+/// Returns the character for a symbol command which is an identifier.
+fn identifier(cmd: &str) -> Option<char> {
+    let ch = match cmd {
+        "infty" => '∞',
+        "emptyset" => '∅',
+        "hbar" => 'ℏ',
+        "ell" => 'ℓ',
+        _ => return None,
+    };
+    Some(ch)
+}
+
+/// Returns the width of a spacing command.
+fn space(cmd: &str) -> Option<&'static str> {
+    let width = match cmd {
+        "," => "0.167em",
+        ":" | ">" => "0.222em",
+        ";" => "0.278em",
+        " " => "0.333em",
+        "quad" => "1em",
+        "qquad" => "2em",
+        _ => return None,
+    };
+    Some(width)
+}
+
+/// Function names which are output upright.
+static FUNCTIONS: &[&str] = &[
+    "sin", "cos", "tan", "cot", "sec", "csc", "arcsin", "arccos", "arctan", "sinh", "cosh", "tanh",
+    "log", "ln", "exp", "lim", "max", "min", "sup", "inf", "det", "gcd",
+];
+
+/// A recursive descent parser over the LaTeX source.
+struct TexParser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl<'a> TexParser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.src[self.pos..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let ch = self.peek()?;
+        self.pos += ch.len_utf8();
+        Some(ch)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(c) if c.is_whitespace()) {
+            self.next();
+        }
+    }
+
+    /// Parses terms until the `close` character, or the end of the source.
+    /// The `close` character is consumed.
+    fn parse_row(&mut self, close: Option<char>) -> String {
+        let mut row = String::new();
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                None => break,
+                Some(ch) if Some(ch) == close => {
+                    self.next();
+                    break;
+                }
+                // Unbalanced close, skip it.
+                Some('}') => {
+                    self.next();
+                }
+                Some(_) => row.push_str(&self.parse_term()),
+            }
+        }
+        row
+    }
+
+    /// Parses an atom along with any sub and superscripts.
+    fn parse_term(&mut self) -> String {
+        let base = self.parse_atom();
+        let mut sub = None;
+        let mut sup = None;
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some('_') if sub.is_none() => {
+                    self.next();
+                    sub = Some(self.parse_arg());
+                }
+                Some('^') if sup.is_none() => {
+                    self.next();
+                    sup = Some(self.parse_arg());
+                }
+                Some('\'') if sup.is_none() => {
+                    self.next();
+                    sup = Some("<mo>′</mo>".to_string());
+                }
+                _ => break,
+            }
+        }
+
+        match (sub, sup) {
+            (None, None) => base,
+            (Some(sub), None) => format!("<msub>{}{}</msub>", base, sub),
+            (None, Some(sup)) => format!("<msup>{}{}</msup>", base, sup),
+            (Some(sub), Some(sup)) => format!("<msubsup>{}{}{}</msubsup>", base, sub, sup),
+        }
+    }
+
+    /// Parses the argument of a command or script. The argument is either a
+    /// `{}` group or a single atom.
+    fn parse_arg(&mut self) -> String {
+        self.skip_whitespace();
+        if self.peek() == Some('{') {
+            self.next();
+            format!("<mrow>{}</mrow>", self.parse_row(Some('}')))
+        } else {
+            self.parse_atom()
+        }
+    }
+
+    /// Reads the raw text of a `{}` group.
+    fn parse_text_arg(&mut self) -> &'a str {
+        self.skip_whitespace();
+        if self.peek() != Some('{') {
+            return "";
+        }
+        self.next();
+
+        let start = self.pos;
+        let mut depth = 1;
+        while let Some(ch) = self.next() {
+            match ch {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return &self.src[start..self.pos - 1];
+                    }
+                }
+                _ => {}
+            }
+        }
+        &self.src[start..]
+    }
+
+    fn parse_atom(&mut self) -> String {
+        self.skip_whitespace();
+        let start = self.pos;
+        match self.next() {
+            None => String::new(),
+            Some('{') => format!("<mrow>{}</mrow>", self.parse_row(Some('}'))),
+            Some('\\') => self.parse_command(),
+            Some(ch) if ch.is_ascii_digit() || ch == '.' => {
+                while matches!(self.peek(), Some(c) if c.is_ascii_digit() || c == '.') {
+                    self.next();
+                }
+                format!("<mn>{}</mn>", &self.src[start..self.pos])
+            }
+            Some(ch) if ch.is_alphabetic() => format!("<mi>{}</mi>", ch),
+            Some(ch) => format!("<mo>{}</mo>", escape(&ch.to_string())),
+        }
+    }
+
+    fn parse_command(&mut self) -> String {
+        let start = self.pos;
+        while matches!(self.peek(), Some(c) if c.is_ascii_alphabetic()) {
+            self.next();
+        }
+        // Commands which are a single non-letter, like `\,` or `\{`.
+        if self.pos == start {
+            self.next();
+        }
+        let cmd = &self.src[start..self.pos];
+
+        if let Some(ch) = greek(cmd).or_else(|| identifier(cmd)) {
+            return format!("<mi>{}</mi>", ch);
+        }
+        if let Some(ch) = operator(cmd) {
+            return format!("<mo>{}</mo>", ch);
+        }
+        if let Some(width) = space(cmd) {
+            return format!("<mspace width=\"{}\"/>", width);
+        }
+        if FUNCTIONS.contains(&cmd) {
+            return format!("<mi mathvariant=\"normal\">{}</mi>", cmd);
+        }
+
+        match cmd {
+            "frac" | "dfrac" | "tfrac" => {
+                let num = self.parse_arg();
+                let den = self.parse_arg();
+                format!("<mfrac>{}{}</mfrac>", num, den)
+            }
+            "sqrt" => {
+                self.skip_whitespace();
+                if self.peek() == Some('[') {
+                    self.next();
+                    let index = self.parse_row(Some(']'));
+                    let base = self.parse_arg();
+                    format!("<mroot>{}<mrow>{}</mrow></mroot>", base, index)
+                } else {
+                    format!("<msqrt>{}</msqrt>", self.parse_arg())
+                }
+            }
+            "text" | "textrm" | "mbox" => {
+                format!("<mtext>{}</mtext>", escape(self.parse_text_arg()))
+            }
+            "mathrm" | "operatorname" => format!(
+                "<mi mathvariant=\"normal\">{}</mi>",
+                escape(self.parse_text_arg())
+            ),
+            "left" | "right" => {
+                self.skip_whitespace();
+                match self.next() {
+                    Some('.') | None => String::new(),
+                    Some('\\') => self.parse_command(),
+                    Some(ch) => format!("<mo>{}</mo>", escape(&ch.to_string())),
+                }
+            }
+            // Line breaks are not supported, treat them as a space.
+            "\\" => "<mspace width=\"1em\"/>".to_string(),
+            _ => format!("<merror><mtext>\\{}</mtext></merror>", escape(cmd)),
+        }
+    }
+}
@@ -0,0 +1,124 @@
+//! The outline of a document, the tree of its headers. Each header is given a
+//! slug id made from its text, which is unique within the document. A repeated
+//! slug has a number added, so the second `Intro` header is `intro-1`.
+
+use crate::tree::{unescape_entity, walk, Block, Doc, Spanned};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// A header in the outline of a document, along with the headers nested
+/// under it.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Heading {
+    /// The level of the header, 1 to 6.
+    pub level: usize,
+    /// The text of the header without any markup.
+    pub title: String,
+    /// The id of the header, unique within the document.
+    pub id: String,
+    /// The headers following this one, up to the next header of the same or a
+    /// lower level.
+    pub children: Vec<Heading>,
+}
+
+/// Returns the slug for a header with the text `title`. The slug is the
+/// lower case title with spaces replaced by `-` and other punctuation removed.
+fn slug(title: &str) -> String {
+    let mut slug = String::with_capacity(title.len());
+    for word in title.split_whitespace() {
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+        slug.extend(
+            word.chars()
+                .filter(|&c| c.is_alphanumeric() || c == '-' || c == '_')
+                .flat_map(char::to_lowercase),
+        );
+    }
+    if slug.is_empty() {
+        slug.push_str("section");
+    }
+    slug
+}
+
+/// Makes the slugs for the headers of a document, adding a number to any slug
+/// which was already used.
+#[derive(Default)]
+pub(crate) struct Slugs {
+    used: HashSet<String>,
+}
+impl Slugs {
+    /// Returns the unique slug for the next header, with the text `title`.
+    pub(crate) fn next(&mut self, title: &str) -> String {
+        let slug = slug(title);
+        let mut id = slug.clone();
+        let mut num = 0;
+        while self.used.contains(&id) {
+            num += 1;
+            id = format!("{}-{}", slug, num);
+        }
+        self.used.insert(id.clone());
+        id
+    }
+}
+
+/// Returns the text of the inline `blocks` of a header, without any markup.
+pub(crate) fn title(blocks: &[Spanned]) -> String {
+    let mut out = String::new();
+    write_title(blocks, &mut out);
+    out.trim().to_string()
+}
+
+fn write_title(blocks: &[Spanned], out: &mut String) {
+    for node in blocks {
+        match &node.block {
+            // The parser replaces these characters with entities in the text.
+            Block::Text(txt) => match unescape_entity(txt) {
+                Some(ch) => out.push(ch),
+                None if txt == "\n" => out.push(' '),
+                None => out.push_str(txt),
+            },
+            Block::InlineMath(src) => out.push_str(src),
+            block => write_title(block.children(), out),
+        }
+    }
+}
+
+/// Returns the outline of `doc`. Headers are nested under the closest header
+/// before them with a lower level.
+pub(crate) fn outline(doc: &Doc) -> Vec<Heading> {
+    let mut outline = vec![];
+    for heading in headers(doc) {
+        insert(&mut outline, heading);
+    }
+    outline
+}
+
+/// Adds `heading` to the end of `outline`, under the last heading if it has a
+/// lower level.
+fn insert(outline: &mut Vec<Heading>, heading: Heading) {
+    match outline.last_mut() {
+        Some(last) if last.level < heading.level => insert(&mut last.children, heading),
+        _ => outline.push(heading),
+    }
+}
+
+/// Returns each of the headers of `doc`, without children, in document order.
+fn headers(doc: &Doc) -> Vec<Heading> {
+    let mut slugs = Slugs::default();
+    let mut headers = vec![];
+    walk(&doc.blocks, &mut |block| {
+        if let Block::Header(level, blocks) = block {
+            let title = title(blocks);
+            headers.push(Heading {
+                level: *level,
+                id: slugs.next(&title),
+                title,
+                children: vec![],
+            });
+        }
+    });
+    headers
+}
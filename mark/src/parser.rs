@@ -12,11 +12,19 @@
 //! The second pass passes any inlines like emphasis as well as resolving link
 //! targets. The output of the second pass a tree of `Block`s which are the
 //! the final representation of the document.
+//!
+//! Each node records the byte range of the source it was parsed from. The
+//! start is the parser position when the node is added, the end is set when
+//! the node is closed. Before building the document each node is extended to
+//! cover its children.
 
 #![allow(clippy::trivial_regex)]
 
-use crate::tree::{Block, Doc, Marker};
+use crate::front_matter;
+use crate::tree::{entity, Alignment, Block, Doc, Marker, Position, Span, Spanned, Task};
 use regex::Regex;
+use std::borrow::Cow;
+use std::collections::HashMap;
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 enum MarkerClose {
@@ -75,13 +83,66 @@ enum Kind<'a> {
     Doc,
     Header(usize /* level */),
     List(ListData),
-    ListElement,
+    ListElement(Option<Task>),
     Paragraph,
     ThematicBreak,
+    Toc,
     RawHtml,
+    Math,
+    Table,
+    TableRow,
+    TableCell(bool /* header */, Alignment),
+    DefinitionList,
+    DefinitionTerm,
+    Definition,
+    /// A footnote definition. The definition is moved to the footnotes section
+    /// of the document when building the document.
+    FootnoteDef(&'a str /* label */),
 
     Text(&'a str),
     Inline(&'a str),
+    Link(&'a str /* href */, Option<&'a str> /* title */),
+    Image(&'a str /* src */, Option<&'a str> /* title */),
+    AutoLink(&'a str),
+    EmailLink(&'a str),
+    NoteLink(&'a str /* target */),
+    InlineMath(&'a str),
+    FootnoteRef(&'a str /* label */),
+    /// A reference link, resolved against the link definitions when building
+    /// the document. The `suffix` is the source after the link text, used to
+    /// output the original text if the reference is not defined.
+    LinkRef(
+        &'a str, /* label */
+        bool,    /* image */
+        &'a str, /* suffix */
+    ),
+}
+impl Kind<'_> {
+    /// Determines if the kind is part of the inline content of a block.
+    fn is_inline(self) -> bool {
+        matches!(
+            self,
+            Kind::Text(_)
+                | Kind::Inline(_)
+                | Kind::Link(..)
+                | Kind::Image(..)
+                | Kind::AutoLink(_)
+                | Kind::EmailLink(_)
+                | Kind::NoteLink(_)
+                | Kind::InlineMath(_)
+                | Kind::FootnoteRef(_)
+                | Kind::LinkRef(..)
+        )
+    }
+}
+
+/// An inline link found in a line of text.
+struct InlineLink<'a> {
+    kind: Kind<'a>,
+    /// The link text, parsed for inlines.
+    text: &'a str,
+    /// The byte offset in the line after the end of the link.
+    end: usize,
 }
 
 /// A node holds information about a given block in the document. The node
@@ -92,27 +153,35 @@ struct Node<'a> {
     kind: Kind<'a>,
     open: bool,
     blocks: Vec<usize>,
+    /// The byte offset of the start of the node in the document.
+    start: usize,
+    /// The byte offset just past the end of the node in the document.
+    end: usize,
 }
 impl<'a> Node<'a> {
-    fn new(kind: Kind<'a>) -> Self {
+    fn new(kind: Kind<'a>, pos: usize) -> Self {
         Self {
             kind,
             open: true,
             blocks: vec![],
+            start: pos,
+            end: pos,
         }
     }
 
     /// Determines if the current node is closed by a node of `kind`.
     fn is_closed_by(&self, kind: Kind) -> bool {
-        if let Kind::Inline(_) = kind {
-            return false;
-        }
-        if let Kind::Text(_) = kind {
+        if kind.is_inline() {
             return false;
         }
 
         match self.kind {
-            Kind::Doc | Kind::Blockquote | Kind::ListElement | Kind::RawHtml => false,
+            Kind::Doc
+            | Kind::Blockquote
+            | Kind::ListElement(_)
+            | Kind::Definition
+            | Kind::RawHtml
+            | Kind::FootnoteDef(_) => false,
             Kind::Paragraph | Kind::Header(_) => kind != Kind::Paragraph,
             _ => true,
         }
@@ -124,9 +193,204 @@ impl<'a> Node<'a> {
     }
 }
 
-fn is_inline_open(ch: char, left: Option<&(usize, char)>, right: Option<&(usize, char)>) -> bool {
+/// Splits a table row into the text of each cell. Leading and trailing pipes
+/// are optional and escaped pipes do not split the row.
+fn split_table_row(line: &'_ str) -> Vec<&'_ str> {
+    let mut row = line.trim();
+    if let Some(rest) = row.strip_prefix('|') {
+        row = rest;
+    }
+    if !row.ends_with("\\|") {
+        if let Some(rest) = row.strip_suffix('|') {
+            row = rest;
+        }
+    }
+
+    let mut cells = vec![];
+    let mut start = 0;
+    let mut escaped = false;
+    for (pos, ch) in row.char_indices() {
+        match ch {
+            '\\' => escaped = !escaped,
+            '|' if !escaped => {
+                cells.push(row[start..pos].trim());
+                start = pos + 1;
+            }
+            _ => escaped = false,
+        }
+    }
+    cells.push(row[start..].trim());
+    cells
+}
+
+/// Normalizes a link reference label so labels match case insensitively and
+/// regardless of internal whitespace.
+fn normalize_label(label: &str) -> String {
+    label
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Strips the surrounding `<>` from a link destination and the quotes or
+/// brackets from a link title.
+fn strip_link_parts<'a>(dest: &'a str, title: Option<&'a str>) -> (&'a str, Option<&'a str>) {
+    let dest = if dest.starts_with('<') {
+        &dest[1..dest.len() - 1]
+    } else {
+        dest
+    };
+    (dest, title.map(|t| &t[1..t.len() - 1]))
+}
+
+/// Removes the backslash from any escaped punctuation in `txt`.
+fn unescape(txt: &str) -> Cow<'_, str> {
+    if !txt.contains('\\') {
+        return Cow::Borrowed(txt);
+    }
+
+    let mut out = String::with_capacity(txt.len());
+    let mut chars = txt.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch == '\\' {
+            if let Some(&next) = chars.peek() {
+                if next.is_ascii_punctuation() {
+                    out.push(next);
+                    chars.next();
+                    continue;
+                }
+            }
+        }
+        out.push(ch);
+    }
+    Cow::Owned(out)
+}
+
+/// Finds the `]` which closes the `[` at byte offset `open` in `line`. Nested
+/// brackets must be balanced and escaped brackets are skipped. Code spans and
+/// HTML tags bind tighter than links so any brackets inside them are skipped.
+fn find_link_text_end(line: &str, open: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut escaped = false;
+    let mut skip_to = 0;
+    for (pos, ch) in line[open..].char_indices() {
+        if pos < skip_to {
+            continue;
+        }
+        match ch {
+            '\\' => {
+                escaped = !escaped;
+                continue;
+            }
+            '`' if !escaped => {
+                if let Some(end) = line[open + pos + 1..].find('`') {
+                    skip_to = pos + end + 2;
+                }
+            }
+            '<' if !escaped => {
+                let tag = line[open + pos + 1..]
+                    .starts_with(|c: char| c.is_ascii_alphabetic() || c == '/');
+                if let (true, Some(end)) = (tag, line[open + pos..].find('>')) {
+                    skip_to = pos + end + 1;
+                }
+            }
+            '[' if !escaped => depth += 1,
+            ']' if !escaped => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(open + pos);
+                }
+            }
+            _ => {}
+        }
+        escaped = false;
+    }
+    None
+}
+
+/// Attempts to parse a note link, `[[note/path]]` or `[[note/path|label]]`,
+/// starting at byte offset `pos` of `line`. Without a label the link text is
+/// the target. Brackets around the link, as in `[[[note]]]`, make it plain
+/// text, as brackets can't be used in a reference link label.
+fn parse_note_link(line: &'_ str, pos: usize) -> Option<InlineLink<'_>> {
+    if line[..pos].ends_with('[') {
+        return None;
+    }
+    let rem = line[pos..].strip_prefix("[[")?;
+    let inner = &rem[..rem.find("]]")?];
+    if rem[inner.len() + 2..].starts_with(']') {
+        return None;
+    }
+    let (target, label) = match inner.find('|') {
+        Some(bar) => (&inner[..bar], &inner[bar + 1..]),
+        None => (inner, ""),
+    };
+
+    let target = target.trim();
+    if target.is_empty() || target.contains(&['[', ']'][..]) {
+        return None;
+    }
+    Some(InlineLink {
+        kind: Kind::NoteLink(target),
+        text: label.trim(),
+        end: pos + inner.len() + 4,
+    })
+}
+
+/// Attempts to parse an autolink, `<https://...>` or `<name@example.com>`,
+/// starting at byte offset `pos` of `line`.
+fn parse_autolink(line: &'_ str, pos: usize) -> Option<InlineLink<'_>> {
+    lazy_static! {
+        static ref URI_RE: Regex =
+            Regex::new(r"^<([A-Za-z][A-Za-z0-9+.\-]{1,31}:[^\s<>]*)>").unwrap();
+        static ref EMAIL_RE: Regex = Regex::new(
+            r"^<([a-zA-Z0-9.!#$%&'*+/=?^_`{|}~\-]+@[a-zA-Z0-9](?:[a-zA-Z0-9\-]{0,61}[a-zA-Z0-9])?(?:\.[a-zA-Z0-9](?:[a-zA-Z0-9\-]{0,61}[a-zA-Z0-9])?)*)>"
+        )
+        .unwrap();
+    }
+
+    let rem = &line[pos..];
+    let (kind, cap) = if let Some(cap) = URI_RE.captures(rem) {
+        (Kind::AutoLink(cap.get(1).unwrap().as_str()), cap)
+    } else if let Some(cap) = EMAIL_RE.captures(rem) {
+        (Kind::EmailLink(cap.get(1).unwrap().as_str()), cap)
+    } else {
+        return None;
+    };
+    Some(InlineLink {
+        kind,
+        text: "",
+        end: pos + cap.get(0).unwrap().end(),
+    })
+}
+
+/// Returns the HTML element for the inline marker `ch`, `double` is set if the
+/// marker is repeated.
+fn inline_element(ch: char, double: bool) -> Option<&'static str> {
+    match (ch, double) {
+        ('_', _) => Some("em"),
+        ('*', _) => Some("strong"),
+        ('`', _) => Some("code"),
+        ('^', _) => Some("sup"),
+        ('~', false) => Some("sub"),
+        ('~', true) => Some("del"),
+        ('=', true) => Some("mark"),
+        _ => None,
+    }
+}
+
+/// Determines if the marker `ch` opens an inline. An `intraword` inline, like
+/// a subscript, can open directly after a word, other inlines must follow
+/// whitespace or the start of the line.
+fn is_inline_open(
+    ch: char,
+    left: Option<&(usize, char)>,
+    right: Option<&(usize, char)>,
+    intraword: bool,
+) -> bool {
     if let Some(&(_, left_char)) = left {
-        if !left_char.is_whitespace() {
+        if !left_char.is_whitespace() && (!intraword || left_char == ch) {
             return false;
         }
     }
@@ -160,42 +424,204 @@ fn is_inline_close(ch: char, left: Option<&(usize, char)>, right: Option<&(usize
     true
 }
 
+/// The footnotes found in the document.
+#[derive(Default)]
+struct Footnotes {
+    /// The definition node for each normalized footnote label.
+    defs: HashMap<String, usize>,
+    /// The normalized labels of the referenced footnotes in the order they are
+    /// numbered, along with the number of references to each.
+    order: Vec<(String, usize)>,
+    /// The footnote number and reference number of each reference node.
+    refs: HashMap<usize, (usize, usize)>,
+}
+
 /// The parser object. Given a string will turn it into a document AST.
 pub struct Parser<'a> {
     root: usize,
     nodes: Vec<Node<'a>>,
     buf: &'a str,
+    /// The byte offset of the start of each line in `buf`.
+    line_starts: Vec<usize>,
+    /// The byte offset in `buf` at which new nodes start.
+    pos: usize,
+    links: HashMap<String, (&'a str, Option<&'a str>)>,
+    footnotes: Footnotes,
 }
 impl<'a, 'b> Parser<'a> {
     /// Create a new parser for the markdown document `str`.
     pub fn new(buf: &'a str) -> Self {
         Self {
             root: 0,
-            nodes: vec![Node::new(Kind::Doc)],
+            nodes: vec![Node::new(Kind::Doc, 0)],
             buf,
+            line_starts: std::iter::once(0)
+                .chain(buf.match_indices('\n').map(|(i, _)| i + 1))
+                .collect(),
+            pos: 0,
+            links: HashMap::new(),
+            footnotes: Footnotes::default(),
         }
     }
 
     /// Parse the document and generate an AST.
     pub fn parse(&mut self) -> Doc<'a> {
         let lines: Vec<&'a str> = self.buf.lines().collect();
-        self.parse_lines(&lines);
-        self.build_doc()
+        let front_matter = front_matter::parse(&lines);
+        let start = front_matter.as_ref().map_or(0, |(_, len)| *len);
+        self.parse_lines(&lines[start..]);
+        let mut doc = self.build_doc();
+        doc.front_matter = front_matter.map(|(front_matter, _)| front_matter);
+        doc
     }
 
     /// Takes the internal node tree and converts to the final AST.
     fn build_doc(&mut self) -> Doc<'a> {
-        let mut blocks = vec![];
-        for idx in &self.nodes[self.root].blocks {
-            blocks.push(self.to_block(*idx));
+        self.number_footnotes();
+        self.finish_spans(self.root);
+
+        let mut blocks = self.convert_blocks(self.root);
+        if !self.footnotes.order.is_empty() {
+            let footnotes: Vec<Spanned<'a>> = self
+                .footnotes
+                .order
+                .iter()
+                .enumerate()
+                .map(|(i, (label, refs))| {
+                    let def = self.footnotes.defs[label];
+                    let footnote = Block::Footnote(i + 1, *refs, self.convert_blocks(def));
+                    self.spanned(footnote, self.nodes[def].start, self.nodes[def].end)
+                })
+                .collect();
+
+            // The footnotes section covers all of the footnote definitions.
+            let start = footnotes.iter().map(|n| n.span.start.offset).min();
+            let end = footnotes.iter().map(|n| n.span.end.offset).max();
+            let section = Block::Footnotes(footnotes);
+            blocks.push(self.spanned(section, start.unwrap_or(0), end.unwrap_or(0)));
         }
         Doc::new(blocks)
     }
 
-    fn convert_blocks(&self, idx: usize) -> Vec<Block<'a>> {
+    /// Extends the node at `idx`, and all of its descendants, to cover the
+    /// source of their children.
+    fn finish_spans(&mut self, idx: usize) {
+        for n in self.nodes[idx].blocks.clone() {
+            self.finish_spans(n);
+            let (start, end) = (self.nodes[n].start, self.nodes[n].end);
+            let node = &mut self.nodes[idx];
+            node.start = node.start.min(start);
+            node.end = node.end.max(end);
+        }
+    }
+
+    /// Returns the line and column of the byte `offset` in the document.
+    fn position(&self, offset: usize) -> Position {
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let line_start = self.line_starts[line - 1];
+        Position {
+            offset,
+            line,
+            column: self.buf[line_start..offset].chars().count() + 1,
+        }
+    }
+
+    /// Wraps `block` with the span of the bytes `start` to `end`.
+    fn spanned(&self, block: Block<'a>, start: usize, end: usize) -> Spanned<'a> {
+        Spanned {
+            block,
+            span: Span {
+                start: self.position(start),
+                end: self.position(end),
+            },
+        }
+    }
+
+    /// Creates a text block for `txt`, which must be a slice of the document.
+    fn text(&self, txt: &'a str) -> Spanned<'a> {
+        let start = self.offset_of(txt).unwrap_or_default();
+        self.spanned(Block::Text(Cow::Borrowed(txt)), start, start + txt.len())
+    }
+
+    /// Numbers the footnotes in the order they are first referenced. The
+    /// references in the document are numbered first, followed by the
+    /// references inside of footnotes.
+    fn number_footnotes(&mut self) {
+        self.number_footnote_refs(self.root);
+
+        let mut idx = 0;
+        while idx < self.footnotes.order.len() {
+            let def = self.footnotes.defs[&self.footnotes.order[idx].0];
+            self.number_footnote_refs(def);
+            idx += 1;
+        }
+    }
+
+    /// Numbers the footnote references under the node at `idx`, skipping any
+    /// footnote definitions.
+    fn number_footnote_refs(&mut self, idx: usize) {
+        for n in self.nodes[idx].blocks.clone() {
+            match self.nodes[n].kind {
+                Kind::FootnoteDef(_) => {}
+                Kind::FootnoteRef(label) => {
+                    let label = normalize_label(label);
+                    if !self.footnotes.defs.contains_key(&label) {
+                        continue;
+                    }
+                    let order = &mut self.footnotes.order;
+                    let num = order
+                        .iter()
+                        .position(|(l, _)| *l == label)
+                        .unwrap_or_else(|| {
+                            order.push((label, 0));
+                            order.len() - 1
+                        });
+                    order[num].1 += 1;
+                    self.footnotes.refs.insert(n, (num + 1, order[num].1));
+                }
+                _ => self.number_footnote_refs(n),
+            }
+        }
+    }
+
+    fn convert_blocks(&self, idx: usize) -> Vec<Spanned<'a>> {
         let mut blocks = vec![];
         for n in &self.nodes[idx].blocks {
-            blocks.push(self.to_block(*n));
+            let node = &self.nodes[*n];
+            match node.kind {
+                // Footnote definitions are output in the footnotes section.
+                Kind::FootnoteDef(_) => continue,
+                Kind::FootnoteRef(label) if !self.footnotes.refs.contains_key(n) => {
+                    // An undefined footnote is output as the original text.
+                    let close = node.end.saturating_sub(1);
+                    blocks.push(self.spanned(
+                        Block::Text(Cow::Borrowed("[^")),
+                        node.start,
+                        node.start + 2,
+                    ));
+                    blocks.push(self.text(label));
+                    blocks.push(self.spanned(Block::Text(Cow::Borrowed("]")), close, node.end));
+                    continue;
+                }
+                _ => {}
+            }
+            if let Kind::LinkRef(label, image, suffix) = node.kind {
+                if !self.links.contains_key(&normalize_label(label)) {
+                    // An undefined reference is output as the original text.
+                    let open = if image { "![" } else { "[" };
+                    let close = node.end.saturating_sub(suffix.len() + 1);
+                    blocks.push(self.spanned(
+                        Block::Text(Cow::Borrowed(open)),
+                        node.start,
+                        node.start + open.len(),
+                    ));
+                    blocks.append(&mut self.convert_blocks(*n));
+                    blocks.push(self.spanned(Block::Text(Cow::Borrowed("]")), close, close + 1));
+                    blocks.push(self.text(suffix));
+                    continue;
+                }
+            }
+            blocks.push(self.spanned(self.to_block(*n), node.start, node.end));
         }
         blocks
     }
@@ -204,18 +630,122 @@ impl<'a, 'b> Parser<'a> {
     fn to_block(&self, idx: usize) -> Block<'a> {
         match self.nodes[idx].kind {
             Kind::Doc => panic!("Should not call to_block on a document"),
-            Kind::Code(lang) => Block::Code(lang, self.convert_blocks(idx)),
+            Kind::FootnoteDef(_) => panic!("Should not call to_block on a footnote definition"),
+            Kind::Code(lang) => Block::Code(lang.map(Cow::Borrowed), self.convert_blocks(idx)),
             Kind::Blockquote => Block::Blockquote(self.convert_blocks(idx)),
             Kind::Header(lvl) => Block::Header(lvl, self.convert_blocks(idx)),
             Kind::List(data) => {
                 Block::List(data.marker, data.start_value, self.convert_blocks(idx))
             }
-            Kind::ListElement => Block::ListElement(self.convert_blocks(idx)),
+            Kind::ListElement(task) => Block::ListElement(task, self.convert_blocks(idx)),
             Kind::Paragraph => Block::Paragraph(self.convert_blocks(idx)),
             Kind::ThematicBreak => Block::ThematicBreak,
-            Kind::Text(txt) => Block::Text(txt),
-            Kind::Inline(el) => Block::Inline(el, self.convert_blocks(idx)),
+            Kind::Toc => Block::Toc,
+            Kind::Text(txt) => Block::Text(Cow::Borrowed(txt)),
+            Kind::Inline(el) => Block::Inline(Cow::Borrowed(el), self.convert_blocks(idx)),
+            Kind::Link(href, title) => Block::Link(
+                unescape(href),
+                title.map(unescape),
+                self.convert_blocks(idx),
+            ),
+            Kind::Image(src, title) => {
+                Block::Image(unescape(src), title.map(unescape), self.convert_blocks(idx))
+            }
+            Kind::AutoLink(url) => Block::Link(Cow::Borrowed(url), None, vec![self.text(url)]),
+            Kind::EmailLink(addr) => Block::Link(
+                Cow::Owned(format!("mailto:{}", addr)),
+                None,
+                vec![self.text(addr)],
+            ),
+            Kind::NoteLink(target) => {
+                let mut blocks = self.convert_blocks(idx);
+                if blocks.is_empty() {
+                    blocks.push(self.text(target));
+                }
+                Block::NoteLink(Cow::Borrowed(target), blocks)
+            }
+            Kind::LinkRef(label, image, _) => {
+                let (href, title) = self.links[&normalize_label(label)];
+                let (href, title) = (unescape(href), title.map(unescape));
+                if image {
+                    Block::Image(href, title, self.convert_blocks(idx))
+                } else {
+                    Block::Link(href, title, self.convert_blocks(idx))
+                }
+            }
             Kind::RawHtml => Block::RawHtml(self.convert_blocks(idx)),
+            Kind::Math => Block::Math(
+                self.nodes[idx]
+                    .blocks
+                    .iter()
+                    .filter_map(|n| match self.nodes[*n].kind {
+                        Kind::Text(txt) => Some(Cow::Borrowed(txt)),
+                        _ => None,
+                    })
+                    .collect(),
+            ),
+            Kind::InlineMath(txt) => Block::InlineMath(Cow::Borrowed(txt)),
+            Kind::FootnoteRef(_) => {
+                let (num, reference) = self.footnotes.refs[&idx];
+                Block::FootnoteRef(num, reference)
+            }
+            Kind::Table => Block::Table(self.convert_blocks(idx)),
+            Kind::TableRow => Block::TableRow(self.convert_blocks(idx)),
+            Kind::TableCell(header, align) => {
+                Block::TableCell(header, align, self.convert_blocks(idx))
+            }
+            Kind::DefinitionList => Block::DefinitionList(self.convert_blocks(idx)),
+            Kind::DefinitionTerm => Block::DefinitionTerm(self.convert_blocks(idx)),
+            Kind::Definition => Block::Definition(self.convert_blocks(idx)),
+        }
+    }
+
+    /// Returns the 1-based line number of the document which contains `txt`.
+    /// The `txt` must be a slice of the document buffer.
+    fn line_of(&self, txt: &str) -> usize {
+        self.position(self.offset_of(txt).unwrap_or_default()).line
+    }
+
+    /// Returns the byte offset of `txt` in the document, if `txt` is a slice
+    /// of the document buffer.
+    fn offset_of(&self, txt: &str) -> Option<usize> {
+        let buf_start = self.buf.as_ptr() as usize;
+        let start = txt.as_ptr() as usize;
+        if start >= buf_start && start + txt.len() <= buf_start + self.buf.len() {
+            Some(start - buf_start)
+        } else {
+            None
+        }
+    }
+
+    /// Moves the parser position to the start of `txt`, if `txt` is a slice
+    /// of the document buffer.
+    fn set_pos(&mut self, txt: &str) {
+        if let Some(offset) = self.offset_of(txt) {
+            self.pos = offset;
+        }
+    }
+
+    /// Moves the parser position to byte `pos` of `line`.
+    fn set_pos_at(&mut self, line: &str, pos: usize) {
+        if let Some(offset) = self.offset_of(line) {
+            self.pos = offset + pos;
+        }
+    }
+
+    /// Ends the node at `idx` at byte `pos` of `line`.
+    fn set_end_at(&mut self, idx: usize, line: &str, pos: usize) {
+        if let Some(offset) = self.offset_of(line) {
+            self.nodes[idx].end = offset + pos;
+        }
+    }
+
+    /// Extends the node at `idx` to the end of `line`, ignoring any trailing
+    /// whitespace.
+    fn end_at_line(&mut self, idx: usize, line: &str) {
+        if let Some(offset) = self.offset_of(line) {
+            let end = offset + line.trim_end().len();
+            self.nodes[idx].end = self.nodes[idx].end.max(end);
         }
     }
 
@@ -235,6 +765,17 @@ impl<'a, 'b> Parser<'a> {
         idx
     }
 
+    /// Finds the parent of the deepest open node in the tree, following the
+    /// open nodes down from `idx` as `find_open_node` does. Returns `None` if
+    /// `idx` itself is the deepest open node.
+    fn find_open_parent(&self, idx: usize) -> Option<usize> {
+        let &child = self.nodes[idx].blocks.last()?;
+        if !self.nodes[child].open {
+            return None;
+        }
+        self.find_open_parent(child).or(Some(idx))
+    }
+
     fn find_parent_list(
         &self,
         idx: usize,
@@ -286,7 +827,7 @@ impl<'a, 'b> Parser<'a> {
     }
 
     fn add_node_to_parent(&mut self, parent: usize, kind: Kind<'a>) -> usize {
-        self.nodes.push(Node::new(kind));
+        self.nodes.push(Node::new(kind, self.pos));
 
         let val = self.nodes.len() - 1;
         self.nodes[parent].blocks.push(val);
@@ -301,8 +842,28 @@ impl<'a, 'b> Parser<'a> {
     }
 
     fn add_text_node(&mut self, txt: &'a str) {
+        self.add_replaced_text_node(txt, txt);
+    }
+
+    /// Adds a text node with `txt` in place of the source text `src`.
+    fn add_replaced_text_node(&mut self, txt: &'a str, src: &str) {
+        self.set_pos(src);
         let idx = self.add_node(Kind::Text(txt));
         self.nodes[idx].open = false;
+        if self.offset_of(src).is_some() {
+            self.pos += src.len();
+            self.nodes[idx].end = self.pos;
+        }
+    }
+
+    /// Adds a line break text node for the newline after `line`.
+    fn add_line_break(&mut self, line: &str) {
+        let newline = self
+            .offset_of(line)
+            .map(|offset| offset + line.len())
+            .and_then(|end| self.buf.get(end..=end))
+            .unwrap_or("");
+        self.add_replaced_text_node("\n", newline);
     }
 
     /// Marks node at `idx` as closed.
@@ -319,6 +880,7 @@ impl<'a, 'b> Parser<'a> {
     fn parse_lines(&mut self, lines: &[&'a str]) {
         let mut idx = 0;
         while idx < lines.len() {
+            self.set_pos(lines[idx]);
             if lines[idx].trim().is_empty() {
                 let node_idx = self.find_open_node(self.root);
                 if self.node_is_closed_by_hardbreak(node_idx) {
@@ -327,22 +889,34 @@ impl<'a, 'b> Parser<'a> {
                 idx += 1;
             } else if self.try_setext_header(&lines, idx).is_some()
                 || self.try_thematic_break(&lines, idx).is_some()
+                || self.try_toc(&lines, idx).is_some()
                 || self.try_header(&lines, idx).is_some()
             {
                 idx += 1;
             } else if let Some(consumed) = self.try_raw_html(&lines, idx) {
                 idx += consumed;
+            } else if let Some(consumed) = self.try_math(&lines, idx) {
+                idx += consumed;
             } else if let Some(consumed) = self.try_fenced_code(&lines, idx) {
                 idx += consumed;
             } else if let Some(consumed) = self.try_blockquote(&lines, idx) {
                 idx += consumed;
             } else if let Some(consumed) = self.try_list(&lines, idx) {
                 idx += consumed;
+            } else if let Some(consumed) = self.try_table(&lines, idx) {
+                idx += consumed;
+            } else if let Some(consumed) = self.try_definition(&lines, idx) {
+                idx += consumed;
+            } else if let Some(consumed) = self.try_footnote_definition(&lines, idx) {
+                idx += consumed;
+            } else if self.try_link_definition(&lines, idx).is_some() {
+                idx += 1;
             } else {
                 let node_idx = self.find_open_node(self.root);
                 if self.nodes[node_idx].kind == Kind::Paragraph {
-                    self.add_text_node("\n");
+                    self.add_line_break(if idx > 0 { lines[idx - 1] } else { "" });
                 } else {
+                    self.set_pos(lines[idx].trim_start());
                     self.add_node(Kind::Paragraph);
                 };
                 self.parse_inlines(lines[idx].trim());
@@ -362,19 +936,152 @@ impl<'a, 'b> Parser<'a> {
         start: usize,
         end: usize,
     ) -> bool {
-        if is_inline_open(ch, prev, next) {
+        let open_node = self.find_open_node(self.root);
+        let open_kind = self.nodes[open_node].kind;
+        // Nothing but the end of a code span is parsed inside of code.
+        if open_kind == Kind::Inline("code") && kind != open_kind {
+            return false;
+        }
+
+        // Superscripts and subscripts can be inside a word, `H~2~O`, but can't
+        // contain whitespace so they must close before the next space.
+        let intraword = kind == Kind::Inline("sup") || kind == Kind::Inline("sub");
+        let closes_in_word = matches!(
+            line[end + ch.len_utf8()..].split(char::is_whitespace).next(),
+            Some(word) if word.contains(ch)
+        );
+
+        if open_kind == kind && is_inline_close(ch, prev, next) {
             self.add_text_node(&line[start..end]);
-            self.add_node(kind);
+            self.close_node(open_node);
+            let marker_len = if kind == Kind::Inline("del") || kind == Kind::Inline("mark") {
+                2
+            } else {
+                ch.len_utf8()
+            };
+            self.set_end_at(open_node, line, end + marker_len);
             true
-        } else if is_inline_close(ch, prev, next) {
+        } else if is_inline_open(ch, prev, next, intraword) && (!intraword || closes_in_word) {
             self.add_text_node(&line[start..end]);
-            self.close_node(self.find_open_node(self.root));
+            self.set_pos_at(line, end);
+            self.add_node(kind);
             true
         } else {
             false
         }
     }
 
+    /// Attempts to parse a link, note link, image or autolink starting at byte offset
+    /// `pos` of `line`. Links are not parsed inside of code.
+    fn parse_link(&self, line: &'a str, pos: usize) -> Option<InlineLink<'a>> {
+        lazy_static! {
+            static ref INLINE_RE: Regex = Regex::new(
+                r#"^\(\s*(<[^<>]*>|[^\s()<>]*(?:\([^\s()]*\)[^\s()<>]*)*)(?:\s+("(?:[^"\\]|\\.)*"|'(?:[^'\\]|\\.)*'|\((?:[^()\\]|\\.)*\)))?\s*\)"#
+            )
+            .unwrap();
+            static ref REF_RE: Regex = Regex::new(r"^\[([^\[\]]*)\]").unwrap();
+            static ref FOOTNOTE_RE: Regex = Regex::new(r"^\[\^([^\[\]\s]+)\]").unwrap();
+        }
+
+        if self.nodes[self.find_open_node(self.root)].kind == Kind::Inline("code") {
+            return None;
+        }
+
+        if let Some(link) = parse_note_link(line, pos) {
+            return Some(link);
+        }
+        if let Some(cap) = FOOTNOTE_RE.captures(&line[pos..]) {
+            return Some(InlineLink {
+                kind: Kind::FootnoteRef(cap.get(1).unwrap().as_str()),
+                text: "",
+                end: pos + cap.get(0).unwrap().end(),
+            });
+        }
+
+        let image = line[pos..].starts_with("![");
+        if line[pos..].starts_with('<') {
+            return parse_autolink(line, pos);
+        } else if !image && !line[pos..].starts_with('[') {
+            return None;
+        }
+
+        let open = if image { pos + 1 } else { pos };
+        let close = find_link_text_end(line, open)?;
+        let text = &line[open + 1..close];
+        let rem = &line[close + 1..];
+
+        // An inline link, `[text](dest "title")`.
+        if let Some(cap) = INLINE_RE.captures(rem) {
+            let (dest, title) =
+                strip_link_parts(cap.get(1).unwrap().as_str(), cap.get(2).map(|t| t.as_str()));
+            let kind = if image {
+                Kind::Image(dest, title)
+            } else {
+                Kind::Link(dest, title)
+            };
+            return Some(InlineLink {
+                kind,
+                text,
+                end: close + 1 + cap.get(0).unwrap().end(),
+            });
+        }
+
+        // A full `[text][label]`, collapsed `[text][]` or shortcut `[text]`
+        // reference link.
+        let (label, suffix) = match REF_RE.captures(rem) {
+            Some(cap) => {
+                let label = cap.get(1).unwrap().as_str();
+                let label = if label.trim().is_empty() { text } else { label };
+                (label, cap.get(0).unwrap().as_str())
+            }
+            None => (text, ""),
+        };
+        // A label can't contain unescaped brackets, so a note link in brackets
+        // isn't a shortcut reference link.
+        let bracketed = label
+            .char_indices()
+            .any(|(i, c)| (c == '[' || c == ']') && !label[..i].ends_with('\\'));
+        if label.trim().is_empty() || bracketed {
+            return None;
+        }
+        Some(InlineLink {
+            kind: Kind::LinkRef(label, image, suffix),
+            text,
+            end: close + 1 + suffix.len(),
+        })
+    }
+
+    /// Finds the `$` which closes the inline math opened by the `$` at byte
+    /// offset `pos` in `line`. The opening `$` must be followed by a non-space
+    /// and the closing `$` must follow a non-space and can't be followed by a
+    /// digit, so prices like $5 and $10 are not math. Math is not parsed inside
+    /// of code and can't contain a code span.
+    fn find_inline_math_end(&self, line: &str, pos: usize) -> Option<usize> {
+        if self.nodes[self.find_open_node(self.root)].kind == Kind::Inline("code") {
+            return None;
+        }
+
+        let rem = &line[pos + 1..];
+        if rem.starts_with(|c: char| c.is_whitespace() || c == '$') {
+            return None;
+        }
+
+        let mut prev = '$';
+        let mut escaped = false;
+        for (i, ch) in rem.char_indices() {
+            if ch == '`' {
+                return None;
+            }
+            if ch == '$' && !escaped && !prev.is_whitespace() && i > 0 {
+                let digit_after = rem[i + 1..].starts_with(|c: char| c.is_ascii_digit());
+                return if digit_after { None } else { Some(pos + 1 + i) };
+            }
+            escaped = ch == '\\' && !escaped;
+            prev = ch;
+        }
+        None
+    }
+
     /// Parses the given line for inline elements
     fn parse_inlines(&mut self, line: &'a str) {
         let chars: Vec<(usize, char)> = line.char_indices().collect();
@@ -387,43 +1094,55 @@ impl<'a, 'b> Parser<'a> {
             let next = chars.get(idx + 1);
             let start = chars[start_idx].0;
             match ch {
-                '_' => {
-                    if self.process_inline_char(
-                        Kind::Inline("em"),
-                        '_',
-                        line,
-                        prev,
-                        next,
-                        start,
-                        pos,
-                    ) {
-                        start_idx = idx + 1;
+                '_' | '*' | '`' | '^' | '~' | '=' => {
+                    // Strikethrough, `~~`, and highlight, `==`, use two
+                    // character markers.
+                    let double = matches!(next, Some(&(_, c)) if c == ch);
+                    let el = inline_element(ch, double);
+                    let (len, next) = match el {
+                        Some("del" | "mark") => (2, chars.get(idx + 2)),
+                        _ => (1, next),
+                    };
+                    if let Some(el) = el {
+                        if self.process_inline_char(
+                            Kind::Inline(el),
+                            ch,
+                            line,
+                            prev,
+                            next,
+                            start,
+                            pos,
+                        ) {
+                            start_idx = idx + len;
+                        }
                     }
+                    idx += len - 1;
                 }
-                '*' => {
-                    if self.process_inline_char(
-                        Kind::Inline("strong"),
-                        '*',
-                        line,
-                        prev,
-                        next,
-                        start,
-                        pos,
-                    ) {
-                        start_idx = idx + 1;
+                '[' | '!' | '<' => {
+                    if let Some(link) = self.parse_link(line, pos) {
+                        self.add_text_node(&line[start..pos]);
+                        self.set_pos_at(line, pos);
+                        let node = self.add_node(link.kind);
+                        self.parse_inlines(link.text);
+                        self.close_node(node);
+                        self.set_end_at(node, line, link.end);
+
+                        idx = chars.partition_point(|&(p, _)| p < link.end);
+                        start_idx = idx;
+                        continue;
                     }
                 }
-                '`' => {
-                    if self.process_inline_char(
-                        Kind::Inline("code"),
-                        '`',
-                        line,
-                        prev,
-                        next,
-                        start,
-                        pos,
-                    ) {
-                        start_idx = idx + 1;
+                '$' => {
+                    if let Some(end) = self.find_inline_math_end(line, pos) {
+                        self.add_text_node(&line[start..pos]);
+                        self.set_pos_at(line, pos);
+                        let node = self.add_node(Kind::InlineMath(&line[pos + 1..end]));
+                        self.close_node(node);
+                        self.set_end_at(node, line, end + 1);
+
+                        idx = chars.partition_point(|&(p, _)| p <= end);
+                        start_idx = idx;
+                        continue;
                     }
                 }
                 '\\' => {
@@ -437,27 +1156,12 @@ impl<'a, 'b> Parser<'a> {
                                 start_idx = idx + 1;
                                 idx += 1;
                             }
-                            '"' => {
-                                self.add_text_node(&line[start..pos]);
-                                self.add_text_node("&quot;");
-                                start_idx = idx + 2;
-                                idx += 1;
-                            }
-                            '&' => {
-                                self.add_text_node(&line[start..pos]);
-                                self.add_text_node("&amp;");
-                                start_idx = idx + 2;
-                                idx += 1;
-                            }
-                            '>' => {
-                                self.add_text_node(&line[start..pos]);
-                                self.add_text_node("&gt;");
-                                start_idx = idx + 2;
-                                idx += 1;
-                            }
-                            '<' => {
+                            '"' | '&' | '>' | '<' => {
                                 self.add_text_node(&line[start..pos]);
-                                self.add_text_node("&lt;");
+                                self.add_replaced_text_node(
+                                    entity(*nxt_ch).unwrap(),
+                                    &line[pos..pos + 2],
+                                );
                                 start_idx = idx + 2;
                                 idx += 1;
                             }
@@ -503,11 +1207,93 @@ impl<'a, 'b> Parser<'a> {
             let node_idx = self.add_node(Kind::Blockquote);
             self.parse_lines(&sub_lines);
             self.close_node(node_idx);
+            self.end_at_line(node_idx, lines[idx + consumed - 1]);
             return Some(consumed);
         }
         None
     }
 
+    /// Attempt to parse a link reference definition, `[label]: dest "title"`.
+    /// The definition is recorded for resolving reference links and does not
+    /// add a node to the document. A definition can not interrupt a paragraph.
+    fn try_link_definition(&mut self, lines: &[&'a str], idx: usize) -> Option<()> {
+        lazy_static! {
+            static ref RE: Regex = Regex::new(
+                r#"^\s{0,3}\[((?:[^\[\]\\]|\\.)+)\]:\s*(<[^<>]*>|[^<\s]\S*)(?:\s+("(?:[^"\\]|\\.)*"|'(?:[^'\\]|\\.)*'|\((?:[^()\\]|\\.)*\)))?\s*$"#
+            )
+            .unwrap();
+        }
+
+        let open_node = self.find_open_node(self.root);
+        if self.nodes[open_node].kind == Kind::Paragraph {
+            return None;
+        }
+
+        let cap = RE.captures(lines[idx])?;
+        let label = normalize_label(cap.get(1).unwrap().as_str());
+        if label.is_empty() {
+            return None;
+        }
+        let (dest, title) =
+            strip_link_parts(cap.get(2).unwrap().as_str(), cap.get(3).map(|t| t.as_str()));
+
+        // The first definition of a label wins.
+        self.links.entry(label).or_insert((dest, title));
+        Some(())
+    }
+
+    /// Attempt to parse a footnote definition, `[^label]: text`. The
+    /// definition continues over any following lines which are indented, so
+    /// a footnote can hold several paragraphs. A definition can not interrupt
+    /// a paragraph. Returns the number of lines consumed.
+    fn try_footnote_definition(&mut self, lines: &[&'a str], idx: usize) -> Option<usize> {
+        lazy_static! {
+            static ref RE: Regex = Regex::new(r"^\s{0,3}\[\^([^\[\]\s]+)\]:\s*(.*)$").unwrap();
+            static ref INDENT_RE: Regex = Regex::new(r"^(?: {1,4}|\t)").unwrap();
+        }
+
+        let cap = RE.captures(lines[idx])?;
+        let label = cap.get(1).unwrap().as_str();
+        let open_node = self.find_open_node(self.root);
+        if self.nodes[open_node].kind == Kind::Paragraph {
+            return None;
+        }
+
+        let mut sub_lines = vec![cap.get(2).unwrap().as_str()];
+        let mut consumed = 1;
+        while idx + consumed < lines.len() {
+            let line = lines[idx + consumed];
+            if line.trim().is_empty() {
+                // Blank lines are only part of the footnote if an indented
+                // line follows them.
+                let next = lines[idx + consumed..]
+                    .iter()
+                    .find(|l| !l.trim().is_empty());
+                if !matches!(next, Some(l) if INDENT_RE.is_match(l)) {
+                    break;
+                }
+                sub_lines.push("");
+            } else if let Some(indent) = INDENT_RE.find(line) {
+                sub_lines.push(&line[indent.end()..]);
+            } else {
+                break;
+            }
+            consumed += 1;
+        }
+
+        let node = self.add_node(Kind::FootnoteDef(label));
+        self.end_at_line(node, lines[idx]);
+        self.parse_lines(&sub_lines);
+        self.close_node(node);
+
+        // The first definition of a label wins.
+        self.footnotes
+            .defs
+            .entry(normalize_label(label))
+            .or_insert(node);
+        Some(consumed)
+    }
+
     /// Attempt to parse a header of up to 6 #'s.
     fn try_header(&mut self, lines: &[&'a str], idx: usize) -> Option<()> {
         lazy_static! {
@@ -525,6 +1311,7 @@ impl<'a, 'b> Parser<'a> {
             let node_idx = self.add_node(Kind::Header(lvl));
             self.parse_inlines(txt);
             self.close_node(node_idx);
+            self.end_at_line(node_idx, lines[idx]);
             return Some(());
         }
         None
@@ -545,6 +1332,7 @@ impl<'a, 'b> Parser<'a> {
 
                 self.nodes[node_idx].kind = Kind::Header(lvl);
                 self.close_node(node_idx);
+                self.end_at_line(node_idx, lines[idx]);
                 return Some(());
             }
         }
@@ -560,6 +1348,18 @@ impl<'a, 'b> Parser<'a> {
         if RE.is_match(lines[idx]) {
             let node_idx = self.add_node(Kind::ThematicBreak);
             self.close_node(node_idx);
+            self.end_at_line(node_idx, lines[idx]);
+            return Some(());
+        }
+        None
+    }
+
+    /// Attempt to parse a `[TOC]` placeholder from `lines`.
+    fn try_toc(&mut self, lines: &[&'a str], idx: usize) -> Option<()> {
+        if lines[idx].trim() == "[TOC]" {
+            let node_idx = self.add_node(Kind::Toc);
+            self.close_node(node_idx);
+            self.end_at_line(node_idx, lines[idx]);
             return Some(());
         }
         None
@@ -612,19 +1412,16 @@ impl<'a, 'b> Parser<'a> {
                     }
                 }
                 if consumed > 1 {
-                    self.add_text_node("\n");
+                    self.add_line_break(lines[idx + consumed - 1]);
                 }
 
                 let line = &lines[idx + consumed];
                 let chars: Vec<(usize, char)> = line.char_indices().collect();
                 let mut char_idx = 0;
 
-                // Skip indent whitespace if present.
-                for _ in 0..indent {
-                    let (_, ch) = chars[char_idx];
-                    if !ch.is_whitespace() {
-                        break;
-                    }
+                // Skip indent whitespace if present, the line may be shorter
+                // than the indent.
+                while char_idx < indent.min(chars.len()) && chars[char_idx].1.is_whitespace() {
                     char_idx += 1;
                 }
 
@@ -633,14 +1430,9 @@ impl<'a, 'b> Parser<'a> {
                     let (pos, ch) = chars[char_idx];
                     let start = chars[start_idx].0;
                     match ch {
-                        '>' => {
+                        '<' | '>' => {
                             self.add_text_node(&line[start..pos]);
-                            self.add_text_node("&gt;");
-                            start_idx = char_idx + 1;
-                        }
-                        '<' => {
-                            self.add_text_node(&line[start..pos]);
-                            self.add_text_node("&lt;");
+                            self.add_replaced_text_node(entity(ch).unwrap(), &line[pos..=pos]);
                             start_idx = char_idx + 1;
                         }
                         _ => {}
@@ -655,11 +1447,63 @@ impl<'a, 'b> Parser<'a> {
             // Make sure to consume the end marker.
             consumed += 1;
             self.close_node(node);
+            self.end_at_line(node, lines[(idx + consumed).min(lines.len()) - 1]);
             return Some(consumed);
         }
         None
     }
 
+    /// Attempt to parse a display math block, starting with `$$`, in `lines`.
+    /// The block runs until a line ending in `$$`, which can be the opening
+    /// line. The math source is kept verbatim, including blank lines. A block
+    /// which is never closed isn't math, like an unclosed `$`.
+    fn try_math(&mut self, lines: &[&'a str], idx: usize) -> Option<usize> {
+        lazy_static! {
+            static ref START_RE: Regex = Regex::new(r"^\s*\$\$(.*)$").unwrap();
+            static ref END_RE: Regex = Regex::new(r"^(.*?)\$\$\s*$").unwrap();
+        }
+
+        let rem = START_RE.captures(lines[idx])?.get(1).unwrap().as_str();
+        let rem = rem.trim_start();
+        let end = if END_RE.is_match(rem) {
+            idx
+        } else {
+            idx + 1
+                + lines[idx + 1..]
+                    .iter()
+                    .position(|line| END_RE.is_match(line))?
+        };
+        let node = self.add_node(Kind::Math);
+
+        if end == idx {
+            self.add_math_edge(END_RE.captures(rem).unwrap().get(1).unwrap().as_str());
+        } else {
+            self.add_math_edge(rem);
+            for line in &lines[idx + 1..end] {
+                self.add_text_node(line.trim_end());
+            }
+            self.add_math_edge(
+                END_RE
+                    .captures(lines[end])
+                    .unwrap()
+                    .get(1)
+                    .unwrap()
+                    .as_str(),
+            );
+        }
+        self.close_node(node);
+        self.end_at_line(node, lines[end]);
+        Some(end + 1 - idx)
+    }
+
+    /// Adds the math source on the line of a `$$` marker to the open math
+    /// block, if there is any.
+    fn add_math_edge(&mut self, line: &'a str) {
+        if !line.trim().is_empty() {
+            self.add_text_node(line.trim_end());
+        }
+    }
+
     fn try_raw_html(&mut self, lines: &[&'a str], idx: usize) -> Option<usize> {
         lazy_static! {
             static ref SCRIPT_PRE_OR_STYLE_OPEN_RE: Regex =
@@ -719,21 +1563,38 @@ impl<'a, 'b> Parser<'a> {
             if close_re.is_match(lines[idx + consumed]) {
                 if !is_custom {
                     self.add_text_node(lines[idx + consumed]);
-                    self.add_text_node("\n");
+                    self.add_line_break(lines[idx + consumed]);
                 }
                 break;
             }
             self.add_text_node(lines[idx + consumed]);
-            self.add_text_node("\n");
+            self.add_line_break(lines[idx + consumed]);
             consumed += 1;
         }
         // Make sure to consume the end marker.
         consumed += 1;
         self.close_node(node);
+        self.end_at_line(node, lines[(idx + consumed).min(lines.len()) - 1]);
 
         Some(consumed)
     }
 
+    /// Determines if the list element content `txt` starts with a `[ ]`, `[x]`
+    /// or `[X]` task marker. Returns the task and the content after the marker.
+    fn parse_task(&self, txt: &'a str) -> Option<(Task, &'a str)> {
+        lazy_static! {
+            static ref RE: Regex = Regex::new(r"^\[([ xX])\]\s").unwrap();
+        }
+
+        RE.captures(txt).map(|cap| {
+            let task = Task {
+                line: self.line_of(txt),
+                checked: cap.get(1).unwrap().as_str() != " ",
+            };
+            (task, &txt[cap.get(0).unwrap().end()..])
+        })
+    }
+
     /// Attempt to parse a list in `lines`. If a list is found, then
     /// consume the lines until the end of the list and returns the number
     /// of lines consumed.
@@ -767,8 +1628,14 @@ impl<'a, 'b> Parser<'a> {
                 return None;
             }
 
+            // A task marker is removed from the element content.
+            let (task, first_line) = match self.parse_task(rem) {
+                Some((task, txt)) => (Some(task), txt),
+                None => (None, &lines[idx][indent..]),
+            };
+
             let mut consumed = 1;
-            let mut sub_lines: Vec<&'a str> = vec![&lines[idx][indent..]];
+            let mut sub_lines: Vec<&'a str> = vec![first_line];
             while idx + consumed < lines.len() {
                 if let Some(space_cap) = SPACE_RE.captures(lines[idx + consumed]) {
                     let start_sp = space_cap.get(1).unwrap().as_str();
@@ -833,7 +1700,9 @@ impl<'a, 'b> Parser<'a> {
                 );
 
                 // Add the element, parse it's contents and then close the element.
-                let li = self.add_node_to_parent(parent_idx, Kind::ListElement);
+                self.set_pos(lines[idx]);
+                let li = self.add_node_to_parent(parent_idx, Kind::ListElement(task));
+                self.end_at_line(li, lines[idx]);
                 self.parse_lines(&sub_lines);
                 self.close_node(li);
                 return Some(consumed);
@@ -841,4 +1710,157 @@ impl<'a, 'b> Parser<'a> {
         }
         None
     }
+
+    /// Attempt to parse a definition, `: definition`, in `lines`. The term is
+    /// the paragraph before the definition, a term can have several
+    /// definitions. The definition continues over any following lines which
+    /// are indented past the `:` marker, so it can hold nested blocks. Returns
+    /// the number of lines consumed.
+    fn try_definition(&mut self, lines: &[&'a str], idx: usize) -> Option<usize> {
+        lazy_static! {
+            static ref RE: Regex = Regex::new(r"^(\s{0,3}:\s+)\S").unwrap();
+            static ref SPACE_RE: Regex = Regex::new(r"^([ \t]*)").unwrap();
+        }
+
+        let indent = RE.captures(lines[idx])?.get(1).unwrap().as_str().len();
+
+        // The term is either the open paragraph or a closed paragraph directly
+        // before the definition. A following definition of the same term
+        // appends to the existing list.
+        let open_node = self.find_open_node(self.root);
+        let parent = if self.nodes[open_node].kind == Kind::Paragraph {
+            self.find_open_parent(self.root)?
+        } else {
+            open_node
+        };
+        let blocks = &self.nodes[parent].blocks;
+        let node = *blocks.last()?;
+        let prev = blocks.len().checked_sub(2).map(|i| blocks[i]);
+        let list = match self.nodes[node].kind {
+            Kind::DefinitionList => node,
+            Kind::Paragraph => {
+                self.nodes[node].kind = Kind::DefinitionTerm;
+                self.close_node(node);
+                self.nodes[parent].blocks.pop();
+
+                // A new term directly after a definition list joins that list.
+                match prev {
+                    Some(prev) if self.nodes[prev].kind == Kind::DefinitionList => {
+                        self.nodes[prev].blocks.push(node);
+                        prev
+                    }
+                    _ => {
+                        let list = self.add_node_to_parent(parent, Kind::DefinitionList);
+                        self.nodes[list].blocks.push(node);
+                        list
+                    }
+                }
+            }
+            _ => return None,
+        };
+
+        let mut consumed = 1;
+        let mut sub_lines: Vec<&'a str> = vec![&lines[idx][indent..]];
+        while idx + consumed < lines.len() {
+            let line = lines[idx + consumed];
+            let is_indented =
+                |l: &str| SPACE_RE.captures(l).unwrap().get(1).unwrap().as_str().len() >= indent;
+            if line.trim().is_empty() {
+                // Blank lines are only part of the definition if an indented
+                // line follows them.
+                let next = lines[idx + consumed..]
+                    .iter()
+                    .find(|l| !l.trim().is_empty());
+                if !matches!(next, Some(l) if is_indented(l)) {
+                    break;
+                }
+                sub_lines.push("");
+            } else if is_indented(line) {
+                sub_lines.push(&line[indent..]);
+            } else {
+                break;
+            }
+            consumed += 1;
+        }
+
+        self.nodes[list].open = true;
+        let def = self.add_node_to_parent(list, Kind::Definition);
+        self.end_at_line(def, lines[idx]);
+        self.parse_lines(&sub_lines);
+        self.close_node(def);
+        self.close_node(list);
+        Some(consumed)
+    }
+
+    /// Attempt to parse a table in `lines`. A table is a header row followed
+    /// by a delimiter row which provides the column alignments. The table
+    /// continues until a blank line or a line without a `|`. A table can not
+    /// interrupt a paragraph.
+    fn try_table(&mut self, lines: &[&'a str], idx: usize) -> Option<usize> {
+        lazy_static! {
+            static ref DELIM_RE: Regex = Regex::new(r"^:?\-+:?$").unwrap();
+        }
+
+        if idx + 1 >= lines.len() || !lines[idx].contains('|') || !lines[idx + 1].contains('|') {
+            return None;
+        }
+
+        // The delimiter row must have a column for each header cell.
+        let header = split_table_row(lines[idx]);
+        let delims = split_table_row(lines[idx + 1]);
+        if header.len() != delims.len() || !delims.iter().all(|d| DELIM_RE.is_match(d)) {
+            return None;
+        }
+        let open_node = self.find_open_node(self.root);
+        if self.nodes[open_node].kind == Kind::Paragraph {
+            return None;
+        }
+
+        let aligns: Vec<Alignment> = delims
+            .iter()
+            .map(|d| match (d.starts_with(':'), d.ends_with(':')) {
+                (true, true) => Alignment::Center,
+                (true, false) => Alignment::Left,
+                (false, true) => Alignment::Right,
+                (false, false) => Alignment::None,
+            })
+            .collect();
+
+        let table = self.add_node(Kind::Table);
+        self.add_table_row(table, lines[idx], &aligns, true);
+        self.end_at_line(table, lines[idx + 1]);
+
+        let mut consumed = 2;
+        while idx + consumed < lines.len() {
+            let line = lines[idx + consumed];
+            if line.trim().is_empty() || !line.contains('|') {
+                break;
+            }
+            self.add_table_row(table, line, &aligns, false);
+            consumed += 1;
+        }
+        self.close_node(table);
+        Some(consumed)
+    }
+
+    /// Adds a row of `cells` to the `table`. The row always has a cell for
+    /// each of the `aligns`, missing cells are left empty and extra cells are
+    /// dropped.
+    fn add_table_row(&mut self, table: usize, line: &'a str, aligns: &[Alignment], header: bool) {
+        let cells = split_table_row(line);
+        self.set_pos(line);
+        let row = self.add_node_to_parent(table, Kind::TableRow);
+        for (i, align) in aligns.iter().enumerate() {
+            if let Some(txt) = cells.get(i) {
+                self.set_pos(txt);
+            }
+            let cell = self.add_node_to_parent(row, Kind::TableCell(header, *align));
+            if let Some(txt) = cells.get(i) {
+                self.parse_inlines(txt);
+            }
+            self.close_node(cell);
+        }
+        self.close_node(row);
+        self.end_at_line(row, line);
+    }
 }
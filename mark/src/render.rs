@@ -0,0 +1,57 @@
+//! Rendering of documents to an output format. A `Renderer` writes a document
+//! to any `fmt::Write` or `io::Write`. The HTML output of the crate is
+//! provided by the `HtmlRenderer`, other formats can be added by implementing
+//! `Renderer`, usually with a `Visitor` over the document.
+
+use crate::tree::Doc;
+use std::fmt;
+use std::io;
+
+/// Renders a document to an output format.
+pub trait Renderer {
+    /// Writes `doc` to `out`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to `out` fails.
+    fn render(&self, doc: &Doc, out: &mut dyn fmt::Write) -> fmt::Result;
+
+    /// Renders `doc` to a string.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the renderer returns an error, writing to a string never
+    /// fails so the error is from the renderer itself.
+    fn render_to_string(&self, doc: &Doc) -> String {
+        let mut out = String::new();
+        self.render(doc, &mut out)
+            .expect("a renderer returned an error writing to a string");
+        out
+    }
+
+    /// Writes `doc` to the `io::Write` `out`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error from `out` if writing to it fails.
+    fn render_to_io(&self, doc: &Doc, out: &mut dyn io::Write) -> io::Result<()> {
+        let mut writer = IoWriter { out, error: None };
+        self.render(doc, &mut writer)
+            .map_err(|_| writer.error.unwrap_or_else(|| io::ErrorKind::Other.into()))
+    }
+}
+
+/// Adapts an `io::Write` to a `fmt::Write`, keeping the `io::Error` which
+/// caused a write to fail.
+struct IoWriter<'w> {
+    out: &'w mut dyn io::Write,
+    error: Option<io::Error>,
+}
+impl fmt::Write for IoWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.out.write_all(s.as_bytes()).map_err(|err| {
+            self.error = Some(err);
+            fmt::Error
+        })
+    }
+}
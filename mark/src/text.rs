@@ -0,0 +1,519 @@
+//! Rendering of documents as text to be read rather than as markup. The
+//! `TextRenderer` writes plain text, for search indexing or the body of an
+//! email, and the `AnsiRenderer` writes the same text styled with ANSI escapes
+//! for reading in a terminal. Inline markup is dropped, links are followed by
+//! their destination and raw HTML is left out.
+
+use crate::render::Renderer;
+use crate::tree::{unescape_entity, Alignment, Block, Doc, Marker, Spanned, Task};
+use crate::visit::Visitor;
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::fmt::{self, Write};
+use std::mem;
+
+// The ANSI select graphic rendition codes used for each kind of block.
+const BOLD: &str = "1";
+const DIM: &str = "2";
+const ITALIC: &str = "3";
+const REVERSE: &str = "7";
+const STRIKE: &str = "9";
+const CODE: &str = "36";
+const LINK: &str = "4;34";
+const MARKER: &str = "33";
+const DONE: &str = "32";
+
+/// The width of a thematic break.
+const RULE_WIDTH: usize = 40;
+
+/// Renders a document as plain text.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TextRenderer;
+
+impl Renderer for TextRenderer {
+    fn render(&self, doc: &Doc, out: &mut dyn fmt::Write) -> fmt::Result {
+        let mut writer = TextWriter::new(false);
+        writer.write_blocks(&doc.blocks)?;
+        out.write_str(&writer.out)
+    }
+}
+
+/// Renders a document as text styled with ANSI escapes, with bold headers,
+/// colored code and list markers and underlined links.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AnsiRenderer;
+
+impl Renderer for AnsiRenderer {
+    fn render(&self, doc: &Doc, out: &mut dyn fmt::Write) -> fmt::Result {
+        let mut writer = TextWriter::new(true);
+        writer.write_blocks(&doc.blocks)?;
+        out.write_str(&writer.out)
+    }
+}
+
+/// Returns the marker for item `idx` of a list. Numbered, lettered and roman
+/// numeral items count up from `start`.
+fn list_marker(marker: Marker, start: u32, idx: usize) -> String {
+    let num = start.saturating_add(u32::try_from(idx).unwrap_or(u32::MAX));
+    match marker {
+        Marker::Bullet | Marker::Dash | Marker::Plus => "•".to_string(),
+        Marker::LowerAlpha if num > 0 => format!("{}.", alpha(b'a', num)),
+        Marker::UpperAlpha if num > 0 => format!("{}.", alpha(b'A', num)),
+        Marker::LowerRoman if num > 0 => format!("{}.", roman(num).to_lowercase()),
+        Marker::UpperRoman if num > 0 => format!("{}.", roman(num)),
+        _ => format!("{}.", num),
+    }
+}
+
+/// Returns the letters for `num` counting from `first`, after `z` comes `aa`.
+fn alpha(first: u8, mut num: u32) -> String {
+    let mut letters = vec![];
+    while num > 0 {
+        num -= 1;
+        letters.push(char::from(first + u8::try_from(num % 26).unwrap_or(0)));
+        num /= 26;
+    }
+    letters.iter().rev().collect()
+}
+
+/// Returns `num` as an upper case roman numeral.
+fn roman(mut num: u32) -> String {
+    const NUMERALS: [(u32, &str); 13] = [
+        (1000, "M"),
+        (900, "CM"),
+        (500, "D"),
+        (400, "CD"),
+        (100, "C"),
+        (90, "XC"),
+        (50, "L"),
+        (40, "XL"),
+        (10, "X"),
+        (9, "IX"),
+        (5, "V"),
+        (4, "IV"),
+        (1, "I"),
+    ];
+    let mut out = String::new();
+    for &(value, numeral) in &NUMERALS {
+        while num >= value {
+            out.push_str(numeral);
+            num -= value;
+        }
+    }
+    out
+}
+
+/// Returns the number of characters of `txt` shown on screen, skipping any
+/// ANSI escapes.
+fn width(txt: &str) -> usize {
+    let mut width = 0;
+    let mut escape = false;
+    for ch in txt.chars() {
+        match ch {
+            '\x1b' => escape = true,
+            'm' if escape => escape = false,
+            _ if escape => {}
+            _ => width += 1,
+        }
+    }
+    width
+}
+
+/// Writes the text for the blocks it visits, with ANSI escapes if `ansi` is
+/// set. Container blocks render their content on its own and then prefix each
+/// line, as the `MarkupRenderer` does.
+struct TextWriter {
+    out: String,
+    ansi: bool,
+    /// The styles of the inlines around the text being written, the innermost
+    /// is last.
+    styles: Vec<&'static str>,
+    /// The marker for the next list element.
+    item: String,
+}
+impl TextWriter {
+    fn new(ansi: bool) -> Self {
+        Self {
+            out: String::new(),
+            ansi,
+            styles: vec![],
+            item: String::new(),
+        }
+    }
+
+    /// Writes each of the `blocks`, separated by blank lines. Blocks which
+    /// write nothing, such as raw HTML, don't add a blank line.
+    fn write_blocks<'v>(&mut self, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        for node in blocks {
+            let len = self.out.len();
+            if len > 0 {
+                writeln!(self.out)?;
+            }
+            let sep = self.out.len();
+            self.visit_node(node)?;
+            if self.out.len() == sep {
+                self.out.truncate(len);
+            }
+        }
+        Ok(())
+    }
+
+    /// Renders the output of `f` on its own.
+    fn render(&mut self, f: impl FnOnce(&mut Self) -> fmt::Result) -> Result<String, fmt::Error> {
+        let out = mem::take(&mut self.out);
+        let res = f(self);
+        let body = mem::replace(&mut self.out, out);
+        res.map(|()| body)
+    }
+
+    /// Returns `txt` with the ANSI `style`, for text outside of any inline.
+    fn paint(&self, style: &str, txt: &str) -> String {
+        if self.ansi && !txt.is_empty() {
+            format!("\x1b[{}m{}\x1b[0m", style, txt)
+        } else {
+            txt.to_string()
+        }
+    }
+
+    /// Returns a horizontal rule `len` characters long.
+    fn rule(&self, len: usize) -> String {
+        if self.ansi {
+            self.paint(DIM, &"─".repeat(len))
+        } else {
+            "-".repeat(len)
+        }
+    }
+
+    /// Writes the output of `f` with the ANSI `style` added to the styles of
+    /// the enclosing inlines.
+    fn styled(
+        &mut self,
+        style: &'static str,
+        f: impl FnOnce(&mut Self) -> fmt::Result,
+    ) -> fmt::Result {
+        if !self.ansi {
+            return f(self);
+        }
+        write!(self.out, "\x1b[{}m", style)?;
+        self.styles.push(style);
+        let res = f(self);
+        self.styles.pop();
+        write!(self.out, "\x1b[0m")?;
+        self.restore_styles()?;
+        res
+    }
+
+    /// Writes the escapes for the styles of the enclosing inlines.
+    fn restore_styles(&mut self) -> fmt::Result {
+        if self.ansi && !self.styles.is_empty() {
+            write!(self.out, "\x1b[{}m", self.styles.join(";"))?;
+        }
+        Ok(())
+    }
+
+    /// Writes `txt` without any control characters, so the source can't
+    /// write its own escapes to the terminal.
+    fn write_text(&mut self, txt: &str) {
+        self.out.extend(
+            txt.chars()
+                .filter(|&c| c == '\t' || c == '\n' || !c.is_control()),
+        );
+    }
+
+    /// Writes the lines of `body` with `first` before the first line and
+    /// `rest` before the others.
+    fn write_prefixed(&mut self, body: &str, first: &str, rest: &str) -> fmt::Result {
+        if body.is_empty() {
+            return writeln!(self.out, "{}", first.trim_end());
+        }
+        for (i, line) in body.lines().enumerate() {
+            let prefix = if i == 0 { first } else { rest };
+            if line.is_empty() {
+                writeln!(self.out, "{}", prefix.trim_end())?;
+            } else {
+                writeln!(self.out, "{}{}", prefix, line)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the `lines` of code or math indented and in the code style.
+    fn write_code(&mut self, lines: &[&str]) -> fmt::Result {
+        for line in lines {
+            if line.is_empty() {
+                writeln!(self.out)?;
+                continue;
+            }
+            self.out.push_str("    ");
+            self.styled(CODE, |w| {
+                w.write_text(line);
+                Ok(())
+            })?;
+            writeln!(self.out)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'v> Visitor<'v> for TextWriter {
+    type Error = fmt::Error;
+
+    fn visit_blockquote(&mut self, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        let body = self.render(|w| w.write_blocks(blocks))?;
+        let prefix = if self.ansi {
+            format!("{} ", self.paint(DIM, "│"))
+        } else {
+            "> ".to_string()
+        };
+        self.write_prefixed(&body, &prefix, &prefix)
+    }
+
+    fn visit_code(&mut self, _lang: Option<&'v str>, lines: &'v [Spanned<'v>]) -> fmt::Result {
+        // The parser replaces `<` and `>` in code, write them back as is.
+        let mut src = String::new();
+        for node in lines {
+            if let Block::Text(txt) = &node.block {
+                match unescape_entity(txt) {
+                    Some(ch) => src.push(ch),
+                    None => src.push_str(txt),
+                }
+            }
+        }
+        self.write_code(&src.lines().collect::<Vec<_>>())
+    }
+
+    fn visit_header(&mut self, lvl: usize, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        let style = match lvl {
+            1 => "1;4;35",
+            2 => "1;35",
+            _ => BOLD,
+        };
+        let txt = self.render(|w| w.styled(style, |w| w.visit_blocks(blocks)))?;
+        writeln!(self.out, "{}", txt)?;
+        // Plain text headers are underlined in the setext style.
+        if !self.ansi && lvl <= 2 {
+            let len = txt.lines().map(width).max().unwrap_or(0);
+            let underline = if lvl == 1 { "=" } else { "-" };
+            writeln!(self.out, "{}", underline.repeat(len))?;
+        }
+        Ok(())
+    }
+
+    fn visit_list(&mut self, marker: Marker, start: u32, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        for (i, node) in blocks.iter().enumerate() {
+            self.item = list_marker(marker, start, i);
+            self.visit_node(node)?;
+        }
+        Ok(())
+    }
+
+    fn visit_list_element(&mut self, task: Option<Task>, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        let marker = mem::take(&mut self.item);
+        let rest = " ".repeat(width(&marker) + 1);
+        let mut first = format!("{} ", self.paint(MARKER, &marker));
+        match task {
+            Some(Task { checked: true, .. }) => {
+                write!(first, "{} ", self.paint(DONE, "[x]"))?;
+            }
+            Some(Task { checked: false, .. }) => write!(first, "{} ", self.paint(DIM, "[ ]"))?,
+            None => {}
+        }
+        let body = self.render(|w| w.write_blocks(blocks))?;
+        self.write_prefixed(&body, &first, &rest)
+    }
+
+    fn visit_paragraph(&mut self, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        self.visit_blocks(blocks)?;
+        writeln!(self.out)
+    }
+
+    fn visit_thematic_break(&mut self) -> fmt::Result {
+        let rule = self.rule(RULE_WIDTH);
+        writeln!(self.out, "{}", rule)
+    }
+
+    fn visit_text(&mut self, txt: &'v str) -> fmt::Result {
+        // The parser replaces these characters with entities in the text.
+        if let Some(ch) = unescape_entity(txt) {
+            self.out.push(ch);
+            return Ok(());
+        }
+        match txt {
+            // The styles end at the end of the line so they aren't applied to
+            // the prefix of the next line.
+            "\n" if self.ansi && !self.styles.is_empty() => {
+                writeln!(self.out, "\x1b[0m")?;
+                self.restore_styles()?;
+            }
+            _ => self.write_text(txt),
+        }
+        Ok(())
+    }
+
+    fn visit_inline(&mut self, el: &'v str, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        let style = match el {
+            "em" => ITALIC,
+            "strong" => BOLD,
+            "code" => CODE,
+            "del" => STRIKE,
+            "mark" => REVERSE,
+            _ => return self.visit_blocks(blocks),
+        };
+        self.styled(style, |w| w.visit_blocks(blocks))
+    }
+
+    fn visit_raw_html(&mut self, _lines: &'v [Spanned<'v>]) -> fmt::Result {
+        Ok(())
+    }
+
+    fn visit_link(
+        &mut self,
+        href: &'v str,
+        _title: Option<&'v str>,
+        blocks: &'v [Spanned<'v>],
+    ) -> fmt::Result {
+        self.styled(LINK, |w| w.visit_blocks(blocks))?;
+
+        // The destination is left off if it is the text of the link.
+        let shown = match blocks {
+            [Spanned {
+                block: Block::Text(txt),
+                ..
+            }] => href == txt || href.strip_prefix("mailto:") == Some(txt),
+            _ => false,
+        };
+        if shown {
+            return Ok(());
+        }
+        self.out.push_str(" (");
+        self.styled(DIM, |w| {
+            w.write_text(href);
+            Ok(())
+        })?;
+        self.out.push(')');
+        Ok(())
+    }
+
+    fn visit_note_link(&mut self, _target: &'v str, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        self.styled(LINK, |w| w.visit_blocks(blocks))
+    }
+
+    fn visit_image(
+        &mut self,
+        _src: &'v str,
+        _title: Option<&'v str>,
+        blocks: &'v [Spanned<'v>],
+    ) -> fmt::Result {
+        self.styled("2;3", |w| w.visit_blocks(blocks))
+    }
+
+    fn visit_math(&mut self, lines: &'v [Cow<'v, str>]) -> fmt::Result {
+        self.write_code(&lines.iter().map(AsRef::as_ref).collect::<Vec<_>>())
+    }
+
+    fn visit_inline_math(&mut self, src: &'v str) -> fmt::Result {
+        self.styled(CODE, |w| {
+            w.write_text(src);
+            Ok(())
+        })
+    }
+
+    fn visit_table(&mut self, rows: &'v [Spanned<'v>]) -> fmt::Result {
+        let mut cells: Vec<Vec<(Alignment, String)>> = vec![];
+        let mut header = false;
+        for row in rows {
+            let mut txts = vec![];
+            for cell in row.block.children() {
+                if let Block::TableCell(head, align, blocks) = &cell.block {
+                    let txt = if *head {
+                        self.render(|w| w.styled(BOLD, |w| w.visit_blocks(blocks)))?
+                    } else {
+                        self.render(|w| w.visit_blocks(blocks))?
+                    };
+                    header |= *head;
+                    txts.push((*align, txt));
+                }
+            }
+            cells.push(txts);
+        }
+
+        let columns = cells.iter().map(Vec::len).max().unwrap_or(0);
+        let widths: Vec<usize> = (0..columns)
+            .map(|col| {
+                cells
+                    .iter()
+                    .filter_map(|row| row.get(col))
+                    .map(|(_, txt)| width(txt))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        for (i, row) in cells.iter().enumerate() {
+            let mut line = String::new();
+            for ((align, txt), &col) in row.iter().zip(&widths) {
+                let pad = col - width(txt);
+                let left = match align {
+                    Alignment::Right => pad,
+                    Alignment::Center => pad / 2,
+                    Alignment::None | Alignment::Left => 0,
+                };
+                write!(
+                    line,
+                    "{}{}{}  ",
+                    " ".repeat(left),
+                    txt,
+                    " ".repeat(pad - left)
+                )?;
+            }
+            writeln!(self.out, "{}", line.trim_end())?;
+            if i == 0 && header {
+                let rules: Vec<String> = widths.iter().map(|&col| self.rule(col)).collect();
+                writeln!(self.out, "{}", rules.join("  "))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_footnote_ref(&mut self, num: usize, _reference: usize) -> fmt::Result {
+        self.styled(DIM, |w| write!(w.out, "[{}]", num))
+    }
+
+    fn visit_definition_list(&mut self, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        for (i, node) in blocks.iter().enumerate() {
+            if i > 0 && matches!(node.block, Block::DefinitionTerm(_)) {
+                writeln!(self.out)?;
+            }
+            self.visit_node(node)?;
+        }
+        Ok(())
+    }
+
+    fn visit_definition_term(&mut self, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        self.styled(BOLD, |w| w.visit_blocks(blocks))?;
+        writeln!(self.out)
+    }
+
+    fn visit_definition(&mut self, blocks: &'v [Spanned<'v>]) -> fmt::Result {
+        let body = self.render(|w| w.write_blocks(blocks))?;
+        self.write_prefixed(&body, "    ", "    ")
+    }
+
+    fn visit_footnotes(&mut self, footnotes: &'v [Spanned<'v>]) -> fmt::Result {
+        let rule = self.rule(RULE_WIDTH / 2);
+        writeln!(self.out, "{}", rule)?;
+        self.write_blocks(footnotes)
+    }
+
+    fn visit_footnote(
+        &mut self,
+        num: usize,
+        _refs: usize,
+        blocks: &'v [Spanned<'v>],
+    ) -> fmt::Result {
+        let body = self.render(|w| w.write_blocks(blocks))?;
+        let first = format!("{} ", self.paint(DIM, &format!("[{}]", num)));
+        let rest = " ".repeat(width(&first));
+        self.write_prefixed(&body, &first, &rest)
+    }
+}
@@ -1,29 +1,133 @@
 //! The abstract syntax tree for the document. The root of the tree is a `Doc`
-//! node, the rest of the tree is made up of `Block` elements.
+//! node, the rest of the tree is made up of `Block` elements. Each block is
+//! held in a `Spanned` node which records where in the source the block came
+//! from.
+//!
+//! The tree borrows text from the source document where it can, use
+//! `Doc::into_owned` to get a tree which owns all of its text. With the `serde`
+//! feature enabled the tree can be serialized and deserialized.
 
+use crate::front_matter::FrontMatter;
+use crate::html::{HtmlRenderer, Options};
+use crate::markup::MarkupRenderer;
+use crate::outline::{self, Heading};
+use crate::render::Renderer;
+use crate::text::TextRenderer;
+use crate::visit::VisitorMut;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::fmt;
 
 /// Representation of a markdown document.
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Doc<'a> {
-    blocks: Vec<Block<'a>>,
+    pub blocks: Vec<Spanned<'a>>,
+    /// The front matter at the start of the document, which is not part of
+    /// the blocks.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub front_matter: Option<FrontMatter>,
 }
 impl<'a> Doc<'a> {
     /// Create a new document with `blocks`
-    pub fn new(blocks: Vec<Block<'a>>) -> Self {
-        Self { blocks }
+    #[must_use]
+    pub fn new(blocks: Vec<Spanned<'a>>) -> Self {
+        Self {
+            blocks,
+            front_matter: None,
+        }
+    }
+
+    /// Returns the top level blocks of the document.
+    #[must_use]
+    pub fn blocks(&self) -> &[Spanned<'a>] {
+        &self.blocks
+    }
+
+    /// Converts the document into one which owns all of its text, so it no
+    /// longer borrows from the source.
+    #[must_use]
+    pub fn into_owned(self) -> Doc<'static> {
+        Doc {
+            blocks: into_owned_blocks(self.blocks),
+            front_matter: self.front_matter,
+        }
+    }
+
+    /// Render the document to HTML using `options`.
+    #[must_use]
+    pub fn to_html(&self, options: &Options) -> String {
+        HtmlRenderer::new(options).render_to_string(self)
+    }
+
+    /// Render the document as normalized mark source.
+    #[must_use]
+    pub fn to_markup(&self) -> String {
+        MarkupRenderer.render_to_string(self)
+    }
+
+    /// Render the document as plain text.
+    #[must_use]
+    pub fn to_text(&self) -> String {
+        TextRenderer.render_to_string(self)
+    }
+
+    /// Determines if the document has the same content as `other`. The
+    /// source spans, the lines of tasks, the bullets of bullet lists and how
+    /// text is split between text blocks are ignored.
+    #[must_use]
+    pub fn same_content(&self, other: &Doc) -> bool {
+        let mut doc = self.clone();
+        let mut other = other.clone();
+        Normalize.visit_doc_mut(&mut doc);
+        Normalize.visit_doc_mut(&mut other);
+        doc == other
+    }
+
+    /// Returns all of the task list items in the document, in document order.
+    #[must_use]
+    pub fn tasks(&self) -> Vec<Task> {
+        let mut tasks = vec![];
+        walk(&self.blocks, &mut |block| {
+            if let Block::ListElement(Some(task), _) = block {
+                tasks.push(*task);
+            }
+        });
+        tasks
+    }
+
+    /// Returns the outline of the document, the tree of its headers. A header
+    /// is nested under the closest header before it with a lower level.
+    #[must_use]
+    pub fn outline(&self) -> Vec<Heading> {
+        outline::outline(self)
+    }
+
+    /// Returns the targets of all of the `[[note]]` links in the document, in
+    /// document order.
+    #[must_use]
+    pub fn note_links(&self) -> Vec<&str> {
+        let mut links = vec![];
+        walk(&self.blocks, &mut |block| {
+            if let Block::NoteLink(target, _) = block {
+                links.push(target.as_ref());
+            }
+        });
+        links
     }
 }
 impl<'a> fmt::Display for Doc<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for idx in 0..self.blocks.len() {
-            write!(f, "{}", self.blocks[idx].to_string())?;
-        }
-        Ok(())
+        HtmlRenderer::default().render(self, f)
     }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Marker {
     Bullet,
     Dash,
@@ -35,99 +139,325 @@ pub enum Marker {
     Numeric,
 }
 
+/// A task list item. The `line` is the 1-based line of the source document
+/// which holds the `[ ]` marker.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Task {
+    pub line: usize,
+    pub checked: bool,
+}
+
+/// The alignment of a table column.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Alignment {
+    None,
+    Left,
+    Center,
+    Right,
+}
+
+/// A position in the source document.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Position {
+    /// The byte offset from the start of the document.
+    pub offset: usize,
+    /// The 1-based line number.
+    pub line: usize,
+    /// The 1-based column, counted in characters.
+    pub column: usize,
+}
+
+/// The range of the source document a block was parsed from. The `end` is
+/// the position just past the last character of the block.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+/// A block along with the span of the source it was parsed from.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Spanned<'a> {
+    pub block: Block<'a>,
+    pub span: Span,
+}
+
 /// The block level elements in the document.
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Block<'a> {
     /// A blockquote containing a set of blocks.
-    Blockquote(Vec<Block<'a>>),
+    Blockquote(Vec<Spanned<'a>>),
     /// A code block. Provides an optional language and the text lines.
-    Code(Option<&'a str>, Vec<Block<'a>>),
+    Code(Option<Cow<'a, str>>, Vec<Spanned<'a>>),
     /// A header with a given level and set of inline text.
-    Header(usize, Vec<Block<'a>>),
-    List(Marker, u32 /* start */, Vec<Block<'a>>),
-    ListElement(Vec<Block<'a>>),
+    Header(usize, Vec<Spanned<'a>>),
+    List(Marker, u32 /* start */, Vec<Spanned<'a>>),
+    /// A list element, if the element is a task list item the task is provided.
+    ListElement(Option<Task>, Vec<Spanned<'a>>),
     /// A paragraph with a given set of inline text.
-    Paragraph(Vec<Block<'a>>),
+    Paragraph(Vec<Spanned<'a>>),
     /// A thematic break.
     ThematicBreak,
+    /// A `[TOC]` placeholder for the table of contents of the document.
+    Toc,
     /// A text block
-    Text(&'a str),
+    Text(Cow<'a, str>),
     /// An inline block
-    Inline(&'a str, Vec<Block<'a>>),
+    Inline(Cow<'a, str>, Vec<Spanned<'a>>),
     /// Raw HTML
-    RawHtml(Vec<Block<'a>>),
+    RawHtml(Vec<Spanned<'a>>),
+    /// A link with a destination, optional title and the link text.
+    Link(Cow<'a, str>, Option<Cow<'a, str>>, Vec<Spanned<'a>>),
+    /// A link to another note with the note target and the link text.
+    NoteLink(Cow<'a, str>, Vec<Spanned<'a>>),
+    /// An image with a source, optional title and the alt text.
+    Image(Cow<'a, str>, Option<Cow<'a, str>>, Vec<Spanned<'a>>),
+    /// A display math block with the lines of the math source.
+    Math(Vec<Cow<'a, str>>),
+    /// Inline math source.
+    InlineMath(Cow<'a, str>),
+    /// A table. The first row is the header row, the rest form the body.
+    Table(Vec<Spanned<'a>>),
+    /// A row of table cells.
+    TableRow(Vec<Spanned<'a>>),
+    /// A table cell with the column alignment and set of inline text.
+    TableCell(bool /* header */, Alignment, Vec<Spanned<'a>>),
+    /// A reference to a footnote with the footnote number and the number of
+    /// this reference to the footnote, starting from 1.
+    FootnoteRef(usize /* number */, usize /* reference */),
+    /// A definition list made up of terms, each followed by their definitions.
+    DefinitionList(Vec<Spanned<'a>>),
+    /// A term in a definition list with a set of inline text.
+    DefinitionTerm(Vec<Spanned<'a>>),
+    /// The definition of a term, containing a set of blocks.
+    Definition(Vec<Spanned<'a>>),
+    /// The footnotes section at the end of the document.
+    Footnotes(Vec<Spanned<'a>>),
+    /// A footnote with the footnote number, the number of references to it
+    /// and the footnote content.
+    Footnote(
+        usize, /* number */
+        usize, /* references */
+        Vec<Spanned<'a>>,
+    ),
+}
+
+impl Block<'_> {
+    /// Determines if the block is inline content, which is part of the text
+    /// of a paragraph, header or other block.
+    #[must_use]
+    pub fn is_inline(&self) -> bool {
+        matches!(
+            self,
+            Block::Text(_)
+                | Block::Inline(..)
+                | Block::Link(..)
+                | Block::NoteLink(..)
+                | Block::Image(..)
+                | Block::InlineMath(_)
+                | Block::FootnoteRef(..)
+        )
+    }
+}
+
+impl Spanned<'_> {
+    /// Converts the node into one which owns all of its text.
+    #[must_use]
+    pub fn into_owned(self) -> Spanned<'static> {
+        Spanned {
+            block: self.block.into_owned(),
+            span: self.span,
+        }
+    }
+}
+
+fn into_owned_blocks(blocks: Vec<Spanned>) -> Vec<Spanned<'static>> {
+    blocks.into_iter().map(Spanned::into_owned).collect()
+}
+
+fn into_owned_str(txt: Cow<str>) -> Cow<'static, str> {
+    Cow::Owned(txt.into_owned())
+}
+
+/// The entities the parser puts in text in place of characters which would
+/// otherwise be read as HTML, escaped characters and `<` and `>` in code.
+const ENTITIES: [(char, &str); 4] = [
+    ('<', "&lt;"),
+    ('>', "&gt;"),
+    ('"', "&quot;"),
+    ('&', "&amp;"),
+];
+
+/// Returns the entity the parser puts in text in place of `ch`, if any.
+pub(crate) fn entity(ch: char) -> Option<&'static str> {
+    ENTITIES
+        .iter()
+        .find(|(c, _)| *c == ch)
+        .map(|(_, entity)| *entity)
+}
+
+/// Returns the character a text block stands for if its text is one of the
+/// entities the parser puts in place of a character.
+pub(crate) fn unescape_entity(txt: &str) -> Option<char> {
+    ENTITIES
+        .iter()
+        .find(|(_, entity)| *entity == txt)
+        .map(|(c, _)| *c)
 }
 
-fn write_blocks<'a>(f: &mut fmt::Formatter, blocks: &[Block<'a>]) -> fmt::Result {
-    for block in blocks.iter() {
-        write!(f, "{}", block.to_string())?;
+/// Calls `f` for each of the `blocks` and all of their descendants, in
+/// document order.
+pub(crate) fn walk<'b, 'a>(blocks: &'b [Spanned<'a>], f: &mut impl FnMut(&'b Block<'a>)) {
+    for node in blocks {
+        f(&node.block);
+        walk(node.block.children(), f);
+    }
+}
+
+/// Clears the source positions from a tree, replaces the bullets of bullet
+/// lists with `*` and joins adjacent text blocks, so trees can be compared by
+/// their content.
+struct Normalize;
+impl<'a> VisitorMut<'a> for Normalize {
+    fn visit_blocks_mut(&mut self, blocks: &mut Vec<Spanned<'a>>) {
+        let mut joined: Vec<Spanned<'a>> = Vec::with_capacity(blocks.len());
+        for mut node in blocks.drain(..) {
+            node.span = Span::default();
+            if let Block::Text(txt) = &node.block {
+                if txt.is_empty() {
+                    continue;
+                }
+                if let Some(Spanned {
+                    block: Block::Text(prev),
+                    ..
+                }) = joined.last_mut()
+                {
+                    prev.to_mut().push_str(txt);
+                    continue;
+                }
+            }
+            joined.push(node);
+        }
+        *blocks = joined;
+
+        for node in blocks {
+            self.visit_node_mut(node);
+        }
+    }
+
+    fn visit_list_mut(
+        &mut self,
+        marker: &mut Marker,
+        _start: &mut u32,
+        blocks: &mut Vec<Spanned<'a>>,
+    ) {
+        if let Marker::Dash | Marker::Plus = marker {
+            *marker = Marker::Bullet;
+        }
+        self.visit_blocks_mut(blocks);
+    }
+
+    fn visit_list_element_mut(&mut self, task: &mut Option<Task>, blocks: &mut Vec<Spanned<'a>>) {
+        if let Some(task) = task {
+            task.line = 0;
+        }
+        self.visit_blocks_mut(blocks);
     }
-    Ok(())
 }
 
 impl<'a> fmt::Display for Block<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        HtmlRenderer::default().render_block(self, f)
+    }
+}
+impl<'a> Block<'a> {
+    /// Returns the child blocks of the block.
+    #[must_use]
+    pub fn children(&self) -> &[Spanned<'a>] {
         match self {
-            Block::Blockquote(blocks) => {
-                writeln!(f, "<blockquote>")?;
-                write_blocks(f, blocks)?;
-                writeln!(f, "</blockquote>")?;
-            }
+            Block::Blockquote(blocks)
+            | Block::Code(_, blocks)
+            | Block::Header(_, blocks)
+            | Block::List(_, _, blocks)
+            | Block::ListElement(_, blocks)
+            | Block::Paragraph(blocks)
+            | Block::Inline(_, blocks)
+            | Block::RawHtml(blocks)
+            | Block::Link(_, _, blocks)
+            | Block::NoteLink(_, blocks)
+            | Block::Image(_, _, blocks)
+            | Block::Table(blocks)
+            | Block::TableRow(blocks)
+            | Block::TableCell(_, _, blocks)
+            | Block::DefinitionList(blocks)
+            | Block::DefinitionTerm(blocks)
+            | Block::Definition(blocks)
+            | Block::Footnotes(blocks)
+            | Block::Footnote(_, _, blocks) => blocks,
+            Block::ThematicBreak
+            | Block::Toc
+            | Block::Text(_)
+            | Block::Math(_)
+            | Block::InlineMath(_)
+            | Block::FootnoteRef(..) => &[],
+        }
+    }
+
+    /// Converts the block into one which owns all of its text.
+    #[must_use]
+    pub fn into_owned(self) -> Block<'static> {
+        match self {
+            Block::Blockquote(blocks) => Block::Blockquote(into_owned_blocks(blocks)),
             Block::Code(lang, lines) => {
-                write!(f, "<pre><code")?;
-                if let Some(lang) = lang {
-                    write!(f, " class=\"language-{}\"", lang)?;
-                }
-                write!(f, ">")?;
-                write_blocks(f, lines)?;
-                if !lines.is_empty() {
-                    writeln!(f,)?;
-                }
-                writeln!(f, "</code></pre>")?;
-            }
-            Block::Header(lvl, content) => {
-                write!(f, "<h{}>", lvl)?;
-                write_blocks(f, content)?;
-                writeln!(f, "</h{}>", lvl)?;
+                Block::Code(lang.map(into_owned_str), into_owned_blocks(lines))
             }
+            Block::Header(lvl, blocks) => Block::Header(lvl, into_owned_blocks(blocks)),
             Block::List(marker, start, blocks) => {
-                let (list, attr) = match marker {
-                    Marker::Bullet | Marker::Dash | Marker::Plus => ("ul", ""),
-                    Marker::UpperAlpha => ("ol", " type='A'"),
-                    Marker::LowerAlpha => ("ol", " type='a'"),
-                    Marker::UpperRoman => ("ol", " type='I'"),
-                    Marker::LowerRoman => ("ol", " type='i'"),
-                    Marker::Numeric => ("ol", ""),
-                };
-                let mut attr = attr.to_string();
-                if *start != 1 {
-                    attr = format!("{} start=\"{}\"", attr, *start);
-                }
-                writeln!(f, "<{}{}>", list, attr)?;
-                write_blocks(f, blocks)?;
-                writeln!(f, "</{}>", list)?;
+                Block::List(marker, start, into_owned_blocks(blocks))
             }
-            Block::ListElement(blocks) => {
-                writeln!(f, "<li>")?;
-                write_blocks(f, blocks)?;
-                writeln!(f, "</li>")?;
+            Block::ListElement(task, blocks) => Block::ListElement(task, into_owned_blocks(blocks)),
+            Block::Paragraph(blocks) => Block::Paragraph(into_owned_blocks(blocks)),
+            Block::ThematicBreak => Block::ThematicBreak,
+            Block::Toc => Block::Toc,
+            Block::Text(txt) => Block::Text(into_owned_str(txt)),
+            Block::Inline(el, blocks) => {
+                Block::Inline(into_owned_str(el), into_owned_blocks(blocks))
             }
-            Block::Paragraph(blocks) => {
-                write!(f, "<p>")?;
-                write_blocks(f, blocks)?;
-                writeln!(f, "</p>")?;
+            Block::RawHtml(lines) => Block::RawHtml(into_owned_blocks(lines)),
+            Block::Link(href, title, blocks) => Block::Link(
+                into_owned_str(href),
+                title.map(into_owned_str),
+                into_owned_blocks(blocks),
+            ),
+            Block::NoteLink(target, blocks) => {
+                Block::NoteLink(into_owned_str(target), into_owned_blocks(blocks))
             }
-            Block::ThematicBreak => writeln!(f, "<hr />")?,
-            Block::Text(txt) => write!(f, "{}", txt)?,
-            Block::Inline(el, blocks) => {
-                write!(f, "<{}>", el)?;
-                write_blocks(f, blocks)?;
-                write!(f, "</{}>", el)?;
+            Block::Image(src, title, blocks) => Block::Image(
+                into_owned_str(src),
+                title.map(into_owned_str),
+                into_owned_blocks(blocks),
+            ),
+            Block::Math(lines) => Block::Math(lines.into_iter().map(into_owned_str).collect()),
+            Block::InlineMath(txt) => Block::InlineMath(into_owned_str(txt)),
+            Block::Table(rows) => Block::Table(into_owned_blocks(rows)),
+            Block::TableRow(cells) => Block::TableRow(into_owned_blocks(cells)),
+            Block::TableCell(header, align, blocks) => {
+                Block::TableCell(header, align, into_owned_blocks(blocks))
             }
-            Block::RawHtml(lines) => {
-                write_blocks(f, lines)?;
+            Block::FootnoteRef(num, reference) => Block::FootnoteRef(num, reference),
+            Block::DefinitionList(blocks) => Block::DefinitionList(into_owned_blocks(blocks)),
+            Block::DefinitionTerm(blocks) => Block::DefinitionTerm(into_owned_blocks(blocks)),
+            Block::Definition(blocks) => Block::Definition(into_owned_blocks(blocks)),
+            Block::Footnotes(blocks) => Block::Footnotes(into_owned_blocks(blocks)),
+            Block::Footnote(num, refs, blocks) => {
+                Block::Footnote(num, refs, into_owned_blocks(blocks))
             }
-        };
-        Ok(())
+        }
     }
 }
@@ -0,0 +1,68 @@
+//! Typographic replacements for document text. Dashes, ellipses and arrows
+//! are replaced with their HTML entities and straight quotes are curled.
+
+use std::borrow::Cow;
+
+/// The character sequences which are replaced, longest first so `---` is
+/// matched before `--`.
+static REPLACEMENTS: &[(&str, &str)] = &[
+    ("<=>", "&DoubleLongLeftRightArrow;"),
+    ("<->", "&harr;"),
+    ("---", "&mdash;"),
+    ("...", "&hellip;"),
+    ("--", "&ndash;"),
+    ("->", "&rarr;"),
+    ("<-", "&larr;"),
+    ("=>", "&rArr;"),
+];
+
+/// Returns `txt` with the typographic replacements applied. Any HTML tags in
+/// the text are left untouched. The `prev` character is the one written
+/// before `txt`, which may be from other text in the same block, and is set
+/// to the last character of `txt`.
+pub fn smarten<'t>(txt: &'t str, prev: &mut Option<char>) -> Cow<'t, str> {
+    if !txt.contains(&['-', '.', '<', '=', '"', '\''][..]) {
+        if let Some(last) = txt.chars().last() {
+            *prev = Some(last);
+        }
+        return Cow::Borrowed(txt);
+    }
+
+    let mut out = String::with_capacity(txt.len());
+    let mut rest = txt;
+    while let Some(ch) = rest.chars().next() {
+        let is_tag = ch == '<'
+            && rest[1..].starts_with(|c: char| c.is_ascii_alphabetic() || c == '/' || c == '!');
+        if let (true, Some(end)) = (is_tag, rest.find('>')) {
+            out.push_str(&rest[..=end]);
+            rest = &rest[end + 1..];
+            *prev = None;
+            continue;
+        }
+
+        if let Some((from, to)) = REPLACEMENTS.iter().find(|(from, _)| rest.starts_with(from)) {
+            out.push_str(to);
+            rest = &rest[from.len()..];
+            *prev = from.chars().last();
+            continue;
+        }
+
+        // A quote opens at the start of the text or after whitespace or an
+        // opening bracket, otherwise it closes. This makes apostrophes close
+        // quotes, as they should be.
+        let opens = match *prev {
+            Some(p) => p.is_whitespace() || "([{".contains(p),
+            None => true,
+        };
+        match (ch, opens) {
+            ('"', true) => out.push_str("&ldquo;"),
+            ('"', false) => out.push_str("&rdquo;"),
+            ('\'', true) => out.push_str("&lsquo;"),
+            ('\'', false) => out.push_str("&rsquo;"),
+            _ => out.push(ch),
+        }
+        *prev = Some(ch);
+        rest = &rest[ch.len_utf8()..];
+    }
+    Cow::Owned(out)
+}
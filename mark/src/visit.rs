@@ -0,0 +1,376 @@
+//! Traversal of the document tree. A `Visitor` walks a tree by reference and
+//! a `VisitorMut` walks it mutably so blocks can be changed in place. Both
+//! have a method for each kind of `Block`, the default implementations visit
+//! the children of the block. Override the methods for the blocks of interest
+//! and call `visit_blocks` from them to continue into the children.
+
+// The errors from the visit methods are the errors of the visitor itself.
+#![allow(clippy::missing_errors_doc)]
+
+use crate::tree::{Alignment, Block, Doc, Marker, Spanned, Task};
+use std::borrow::Cow;
+
+/// Visits the blocks of a document by reference. Each method returns a
+/// `Result` so visitors which write output can stop on the first error,
+/// visitors which can't fail can use `std::convert::Infallible`.
+pub trait Visitor<'v> {
+    type Error;
+
+    /// Visits each of the top level blocks of `doc`.
+    fn visit_doc(&mut self, doc: &'v Doc<'v>) -> Result<(), Self::Error> {
+        self.visit_blocks(&doc.blocks)
+    }
+
+    /// Visits each of the `blocks` in order.
+    fn visit_blocks(&mut self, blocks: &'v [Spanned<'v>]) -> Result<(), Self::Error> {
+        for node in blocks {
+            self.visit_node(node)?;
+        }
+        Ok(())
+    }
+
+    /// Visits a block along with its source span. Override this to see the
+    /// span of each block, calling `walk_block` to visit the block itself.
+    fn visit_node(&mut self, node: &'v Spanned<'v>) -> Result<(), Self::Error> {
+        self.walk_block(&node.block)
+    }
+
+    /// Calls the visit method for the kind of `block`.
+    fn walk_block(&mut self, block: &'v Block<'v>) -> Result<(), Self::Error> {
+        match block {
+            Block::Blockquote(blocks) => self.visit_blockquote(blocks),
+            Block::Code(lang, lines) => self.visit_code(lang.as_deref(), lines),
+            Block::Header(lvl, blocks) => self.visit_header(*lvl, blocks),
+            Block::List(marker, start, blocks) => self.visit_list(*marker, *start, blocks),
+            Block::ListElement(task, blocks) => self.visit_list_element(*task, blocks),
+            Block::Paragraph(blocks) => self.visit_paragraph(blocks),
+            Block::ThematicBreak => self.visit_thematic_break(),
+            Block::Toc => self.visit_toc(),
+            Block::Text(txt) => self.visit_text(txt),
+            Block::Inline(el, blocks) => self.visit_inline(el, blocks),
+            Block::RawHtml(lines) => self.visit_raw_html(lines),
+            Block::Link(href, title, blocks) => self.visit_link(href, title.as_deref(), blocks),
+            Block::NoteLink(target, blocks) => self.visit_note_link(target, blocks),
+            Block::Image(src, title, blocks) => self.visit_image(src, title.as_deref(), blocks),
+            Block::Math(lines) => self.visit_math(lines),
+            Block::InlineMath(src) => self.visit_inline_math(src),
+            Block::Table(rows) => self.visit_table(rows),
+            Block::TableRow(cells) => self.visit_table_row(cells),
+            Block::TableCell(header, align, blocks) => {
+                self.visit_table_cell(*header, *align, blocks)
+            }
+            Block::FootnoteRef(num, reference) => self.visit_footnote_ref(*num, *reference),
+            Block::DefinitionList(blocks) => self.visit_definition_list(blocks),
+            Block::DefinitionTerm(blocks) => self.visit_definition_term(blocks),
+            Block::Definition(blocks) => self.visit_definition(blocks),
+            Block::Footnotes(footnotes) => self.visit_footnotes(footnotes),
+            Block::Footnote(num, refs, blocks) => self.visit_footnote(*num, *refs, blocks),
+        }
+    }
+
+    fn visit_blockquote(&mut self, blocks: &'v [Spanned<'v>]) -> Result<(), Self::Error> {
+        self.visit_blocks(blocks)
+    }
+
+    fn visit_code(
+        &mut self,
+        _lang: Option<&'v str>,
+        lines: &'v [Spanned<'v>],
+    ) -> Result<(), Self::Error> {
+        self.visit_blocks(lines)
+    }
+
+    fn visit_header(&mut self, _lvl: usize, blocks: &'v [Spanned<'v>]) -> Result<(), Self::Error> {
+        self.visit_blocks(blocks)
+    }
+
+    fn visit_list(
+        &mut self,
+        _marker: Marker,
+        _start: u32,
+        blocks: &'v [Spanned<'v>],
+    ) -> Result<(), Self::Error> {
+        self.visit_blocks(blocks)
+    }
+
+    fn visit_list_element(
+        &mut self,
+        _task: Option<Task>,
+        blocks: &'v [Spanned<'v>],
+    ) -> Result<(), Self::Error> {
+        self.visit_blocks(blocks)
+    }
+
+    fn visit_paragraph(&mut self, blocks: &'v [Spanned<'v>]) -> Result<(), Self::Error> {
+        self.visit_blocks(blocks)
+    }
+
+    fn visit_thematic_break(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn visit_toc(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn visit_text(&mut self, _txt: &'v str) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn visit_inline(&mut self, _el: &'v str, blocks: &'v [Spanned<'v>]) -> Result<(), Self::Error> {
+        self.visit_blocks(blocks)
+    }
+
+    fn visit_raw_html(&mut self, lines: &'v [Spanned<'v>]) -> Result<(), Self::Error> {
+        self.visit_blocks(lines)
+    }
+
+    fn visit_link(
+        &mut self,
+        _href: &'v str,
+        _title: Option<&'v str>,
+        blocks: &'v [Spanned<'v>],
+    ) -> Result<(), Self::Error> {
+        self.visit_blocks(blocks)
+    }
+
+    fn visit_note_link(
+        &mut self,
+        _target: &'v str,
+        blocks: &'v [Spanned<'v>],
+    ) -> Result<(), Self::Error> {
+        self.visit_blocks(blocks)
+    }
+
+    fn visit_image(
+        &mut self,
+        _src: &'v str,
+        _title: Option<&'v str>,
+        blocks: &'v [Spanned<'v>],
+    ) -> Result<(), Self::Error> {
+        self.visit_blocks(blocks)
+    }
+
+    fn visit_math(&mut self, _lines: &'v [Cow<'v, str>]) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn visit_inline_math(&mut self, _src: &'v str) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn visit_table(&mut self, rows: &'v [Spanned<'v>]) -> Result<(), Self::Error> {
+        self.visit_blocks(rows)
+    }
+
+    fn visit_table_row(&mut self, cells: &'v [Spanned<'v>]) -> Result<(), Self::Error> {
+        self.visit_blocks(cells)
+    }
+
+    fn visit_table_cell(
+        &mut self,
+        _header: bool,
+        _align: Alignment,
+        blocks: &'v [Spanned<'v>],
+    ) -> Result<(), Self::Error> {
+        self.visit_blocks(blocks)
+    }
+
+    fn visit_footnote_ref(&mut self, _num: usize, _reference: usize) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn visit_definition_list(&mut self, blocks: &'v [Spanned<'v>]) -> Result<(), Self::Error> {
+        self.visit_blocks(blocks)
+    }
+
+    fn visit_definition_term(&mut self, blocks: &'v [Spanned<'v>]) -> Result<(), Self::Error> {
+        self.visit_blocks(blocks)
+    }
+
+    fn visit_definition(&mut self, blocks: &'v [Spanned<'v>]) -> Result<(), Self::Error> {
+        self.visit_blocks(blocks)
+    }
+
+    fn visit_footnotes(&mut self, footnotes: &'v [Spanned<'v>]) -> Result<(), Self::Error> {
+        self.visit_blocks(footnotes)
+    }
+
+    fn visit_footnote(
+        &mut self,
+        _num: usize,
+        _refs: usize,
+        blocks: &'v [Spanned<'v>],
+    ) -> Result<(), Self::Error> {
+        self.visit_blocks(blocks)
+    }
+}
+
+/// Visits the blocks of a document mutably. The blocks can be changed in
+/// place, and `visit_blocks_mut` can add or remove blocks from a set of
+/// children.
+pub trait VisitorMut<'a> {
+    /// Visits each of the top level blocks of `doc`.
+    fn visit_doc_mut(&mut self, doc: &mut Doc<'a>) {
+        self.visit_blocks_mut(&mut doc.blocks);
+    }
+
+    /// Visits each of the `blocks` in order.
+    fn visit_blocks_mut(&mut self, blocks: &mut Vec<Spanned<'a>>) {
+        for node in blocks {
+            self.visit_node_mut(node);
+        }
+    }
+
+    /// Visits a block along with its source span. Override this to replace
+    /// whole blocks, calling `walk_block_mut` to visit the block itself.
+    fn visit_node_mut(&mut self, node: &mut Spanned<'a>) {
+        self.walk_block_mut(&mut node.block);
+    }
+
+    /// Calls the visit method for the kind of `block`.
+    fn walk_block_mut(&mut self, block: &mut Block<'a>) {
+        match block {
+            Block::Blockquote(blocks) => self.visit_blockquote_mut(blocks),
+            Block::Code(lang, lines) => self.visit_code_mut(lang, lines),
+            Block::Header(lvl, blocks) => self.visit_header_mut(lvl, blocks),
+            Block::List(marker, start, blocks) => self.visit_list_mut(marker, start, blocks),
+            Block::ListElement(task, blocks) => self.visit_list_element_mut(task, blocks),
+            Block::Paragraph(blocks) => self.visit_paragraph_mut(blocks),
+            Block::ThematicBreak => self.visit_thematic_break_mut(),
+            Block::Toc => self.visit_toc_mut(),
+            Block::Text(txt) => self.visit_text_mut(txt),
+            Block::Inline(el, blocks) => self.visit_inline_mut(el, blocks),
+            Block::RawHtml(lines) => self.visit_raw_html_mut(lines),
+            Block::Link(href, title, blocks) => self.visit_link_mut(href, title, blocks),
+            Block::NoteLink(target, blocks) => self.visit_note_link_mut(target, blocks),
+            Block::Image(src, title, blocks) => self.visit_image_mut(src, title, blocks),
+            Block::Math(lines) => self.visit_math_mut(lines),
+            Block::InlineMath(src) => self.visit_inline_math_mut(src),
+            Block::Table(rows) => self.visit_table_mut(rows),
+            Block::TableRow(cells) => self.visit_table_row_mut(cells),
+            Block::TableCell(header, align, blocks) => {
+                self.visit_table_cell_mut(header, align, blocks);
+            }
+            Block::FootnoteRef(num, reference) => self.visit_footnote_ref_mut(num, reference),
+            Block::DefinitionList(blocks) => self.visit_definition_list_mut(blocks),
+            Block::DefinitionTerm(blocks) => self.visit_definition_term_mut(blocks),
+            Block::Definition(blocks) => self.visit_definition_mut(blocks),
+            Block::Footnotes(footnotes) => self.visit_footnotes_mut(footnotes),
+            Block::Footnote(num, refs, blocks) => self.visit_footnote_mut(num, refs, blocks),
+        }
+    }
+
+    fn visit_blockquote_mut(&mut self, blocks: &mut Vec<Spanned<'a>>) {
+        self.visit_blocks_mut(blocks);
+    }
+
+    fn visit_code_mut(&mut self, _lang: &mut Option<Cow<'a, str>>, lines: &mut Vec<Spanned<'a>>) {
+        self.visit_blocks_mut(lines);
+    }
+
+    fn visit_header_mut(&mut self, _lvl: &mut usize, blocks: &mut Vec<Spanned<'a>>) {
+        self.visit_blocks_mut(blocks);
+    }
+
+    fn visit_list_mut(
+        &mut self,
+        _marker: &mut Marker,
+        _start: &mut u32,
+        blocks: &mut Vec<Spanned<'a>>,
+    ) {
+        self.visit_blocks_mut(blocks);
+    }
+
+    fn visit_list_element_mut(&mut self, _task: &mut Option<Task>, blocks: &mut Vec<Spanned<'a>>) {
+        self.visit_blocks_mut(blocks);
+    }
+
+    fn visit_paragraph_mut(&mut self, blocks: &mut Vec<Spanned<'a>>) {
+        self.visit_blocks_mut(blocks);
+    }
+
+    fn visit_thematic_break_mut(&mut self) {}
+
+    fn visit_toc_mut(&mut self) {}
+
+    fn visit_text_mut(&mut self, _txt: &mut Cow<'a, str>) {}
+
+    fn visit_inline_mut(&mut self, _el: &mut Cow<'a, str>, blocks: &mut Vec<Spanned<'a>>) {
+        self.visit_blocks_mut(blocks);
+    }
+
+    fn visit_raw_html_mut(&mut self, lines: &mut Vec<Spanned<'a>>) {
+        self.visit_blocks_mut(lines);
+    }
+
+    fn visit_link_mut(
+        &mut self,
+        _href: &mut Cow<'a, str>,
+        _title: &mut Option<Cow<'a, str>>,
+        blocks: &mut Vec<Spanned<'a>>,
+    ) {
+        self.visit_blocks_mut(blocks);
+    }
+
+    fn visit_note_link_mut(&mut self, _target: &mut Cow<'a, str>, blocks: &mut Vec<Spanned<'a>>) {
+        self.visit_blocks_mut(blocks);
+    }
+
+    fn visit_image_mut(
+        &mut self,
+        _src: &mut Cow<'a, str>,
+        _title: &mut Option<Cow<'a, str>>,
+        blocks: &mut Vec<Spanned<'a>>,
+    ) {
+        self.visit_blocks_mut(blocks);
+    }
+
+    fn visit_math_mut(&mut self, _lines: &mut Vec<Cow<'a, str>>) {}
+
+    fn visit_inline_math_mut(&mut self, _src: &mut Cow<'a, str>) {}
+
+    fn visit_table_mut(&mut self, rows: &mut Vec<Spanned<'a>>) {
+        self.visit_blocks_mut(rows);
+    }
+
+    fn visit_table_row_mut(&mut self, cells: &mut Vec<Spanned<'a>>) {
+        self.visit_blocks_mut(cells);
+    }
+
+    fn visit_table_cell_mut(
+        &mut self,
+        _header: &mut bool,
+        _align: &mut Alignment,
+        blocks: &mut Vec<Spanned<'a>>,
+    ) {
+        self.visit_blocks_mut(blocks);
+    }
+
+    fn visit_footnote_ref_mut(&mut self, _num: &mut usize, _reference: &mut usize) {}
+
+    fn visit_definition_list_mut(&mut self, blocks: &mut Vec<Spanned<'a>>) {
+        self.visit_blocks_mut(blocks);
+    }
+
+    fn visit_definition_term_mut(&mut self, blocks: &mut Vec<Spanned<'a>>) {
+        self.visit_blocks_mut(blocks);
+    }
+
+    fn visit_definition_mut(&mut self, blocks: &mut Vec<Spanned<'a>>) {
+        self.visit_blocks_mut(blocks);
+    }
+
+    fn visit_footnotes_mut(&mut self, footnotes: &mut Vec<Spanned<'a>>) {
+        self.visit_blocks_mut(footnotes);
+    }
+
+    fn visit_footnote_mut(
+        &mut self,
+        _num: &mut usize,
+        _refs: &mut usize,
+        blocks: &mut Vec<Spanned<'a>>,
+    ) {
+        self.visit_blocks_mut(blocks);
+    }
+}
@@ -0,0 +1,496 @@
+use mark::Task;
+use pretty_assertions::assert_eq;
+
+#[test]
+pub fn tasks() {
+    let src = "# Todo\n * [ ] First\n * [x] Second\n\n > - [X] Quoted\n\n1. [ ] Ordered\n";
+    assert_eq!(
+        mark::tasks(src),
+        vec![
+            Task {
+                line: 2,
+                checked: false
+            },
+            Task {
+                line: 3,
+                checked: true
+            },
+            Task {
+                line: 5,
+                checked: true
+            },
+            Task {
+                line: 7,
+                checked: false
+            },
+        ]
+    );
+}
+
+struct Notes;
+impl mark::Resolver for Notes {
+    fn href(&self, target: &str) -> String {
+        format!("/note/{}", target)
+    }
+
+    fn exists(&self, target: &str) -> bool {
+        target == "present"
+    }
+}
+
+#[test]
+pub fn note_link_resolver() {
+    let opts = mark::Options {
+        resolver: Some(&Notes),
+        ..mark::Options::default()
+    };
+    assert_eq!(
+        mark::to_html_with("[[present]] [[absent|Gone]]", &opts),
+        "<p><a href=\"/note/present\">present</a> <a href=\"/note/absent\" class=\"missing\">Gone</a></p>\n"
+    );
+}
+
+#[test]
+pub fn note_links() {
+    let src = "# [[title]]\n\n* [[a/b|B]]\n\n| [[c]] |\n|---|\n| [[d]] |\n";
+    assert_eq!(
+        mark::to_ast(src).note_links(),
+        vec!["title", "a/b", "c", "d"]
+    );
+    assert!(mark::to_ast("[[[e]]]\n").note_links().is_empty());
+}
+
+#[test]
+#[cfg(feature = "mathml")]
+pub fn mathml() {
+    let opts = mark::Options {
+        math: mark::MathStyle::MathMl,
+        ..mark::Options::default()
+    };
+    assert_eq!(
+        mark::to_html_with("$x^2$\n\n$$\n\\frac{1}{\\sqrt{n}} \\leq \\alpha_{i}\n$$\n", &opts),
+        concat!(
+            "<p><math xmlns=\"http://www.w3.org/1998/Math/MathML\" display=\"inline\">",
+            "<mrow><msup><mi>x</mi><mn>2</mn></msup></mrow></math></p>\n",
+            "<math xmlns=\"http://www.w3.org/1998/Math/MathML\" display=\"block\"><mrow>",
+            "<mfrac><mrow><mn>1</mn></mrow><mrow><msqrt><mrow><mi>n</mi></mrow></msqrt></mrow></mfrac>",
+            "<mo>≤</mo><msub><mi>α</mi><mrow><mi>i</mi></mrow></msub></mrow></math>\n"
+        )
+    );
+}
+
+#[test]
+pub fn typography() {
+    let opts = mark::Options {
+        typography: true,
+        ..mark::Options::default()
+    };
+    assert_eq!(
+        mark::to_html_with(
+            "\"Pages\" 1--2 --- it's <b>'x'</b>... a -> b <=> c\n\n`a -- \"b\"`\n\n```\n--\n```\n",
+            &opts
+        ),
+        concat!(
+            "<p>&ldquo;Pages&rdquo; 1&ndash;2 &mdash; it&rsquo;s <b>&lsquo;x&rsquo;</b>&hellip; ",
+            "a &rarr; b &DoubleLongLeftRightArrow; c</p>\n",
+            "<p><code>a -- \"b\"</code></p>\n",
+            "<pre><code>--\n</code></pre>\n"
+        )
+    );
+}
+
+#[test]
+pub fn typography_across_inlines() {
+    let opts = mark::Options {
+        typography: true,
+        ..mark::Options::default()
+    };
+    let html = |src| mark::to_html_with(src, &opts);
+    assert_eq!(
+        html("say \"hi *there*\" ok\n"),
+        "<p>say &ldquo;hi <strong>there</strong>&rdquo; ok</p>\n"
+    );
+    assert_eq!(
+        html("*Bob*'s and \"a *Bob*\"\n"),
+        "<p><strong>Bob</strong>&rsquo;s and &ldquo;a <strong>Bob</strong>&rdquo;</p>\n"
+    );
+    assert_eq!(
+        html("`x`'s \"a `y`\"\n"),
+        "<p><code>x</code>&rsquo;s &ldquo;a <code>y</code>&rdquo;</p>\n"
+    );
+    assert_eq!(
+        html("[a](u)'s \"a [b](u)\"\n"),
+        "<p><a href=\"u\">a</a>&rsquo;s &ldquo;a <a href=\"u\">b</a>&rdquo;</p>\n"
+    );
+    assert_eq!(
+        html("End.\n\n'b'\n"),
+        "<p>End.</p>\n<p>&lsquo;b&rsquo;</p>\n"
+    );
+    assert_eq!(
+        html("see <http://a.com/a--b> and <a@b--c.com>\n"),
+        concat!(
+            "<p>see <a href=\"http://a.com/a--b\">http://a.com/a--b</a> ",
+            "and <a href=\"mailto:a@b--c.com\">a@b--c.com</a></p>\n"
+        )
+    );
+}
+
+#[test]
+pub fn spans() {
+    let doc = mark::to_ast("# Title\n\n> Some *em*\n> ü `c`\n");
+    let span = |node: &mark::Spanned| {
+        let (start, end) = (node.span.start, node.span.end);
+        (
+            (start.offset, start.line, start.column),
+            (end.offset, end.line, end.column),
+        )
+    };
+
+    let blocks = doc.blocks();
+    assert_eq!(span(&blocks[0]), ((0, 1, 1), (7, 1, 8)));
+    assert_eq!(span(&blocks[0].block.children()[0]), ((2, 1, 3), (7, 1, 8)));
+
+    let quote = &blocks[1];
+    assert_eq!(span(quote), ((9, 3, 1), (29, 4, 8)));
+    let para = &quote.block.children()[0];
+    assert_eq!(span(para), ((11, 3, 3), (29, 4, 8)));
+    let inlines: Vec<_> = para.block.children().iter().map(span).collect();
+    assert_eq!(
+        inlines,
+        vec![
+            ((11, 3, 3), (16, 3, 8)),
+            ((16, 3, 8), (20, 3, 12)),
+            ((20, 3, 12), (21, 4, 1)),
+            ((23, 4, 3), (26, 4, 5)),
+            ((26, 4, 5), (29, 4, 8)),
+        ]
+    );
+}
+
+#[test]
+pub fn into_owned() {
+    let src = String::from("# [[Title]]\n\n```rust\nfn x() {}\n```\n\n$x$ and *em*\n");
+    let html = mark::to_html(&src);
+    let doc = mark::to_ast(&src).into_owned();
+    drop(src);
+    assert_eq!(doc.to_html(&mark::Options::default()), html);
+}
+
+#[test]
+#[cfg(feature = "serde")]
+pub fn serde() {
+    let doc = mark::to_ast("Some *em* text\n\n- [x] Done\n");
+    let json = serde_json::to_string(&doc).unwrap();
+    let parsed: mark::Doc = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed, doc);
+}
+
+/// Counts the words in the text of a document and collects the headers.
+#[derive(Default)]
+struct Outline<'v> {
+    words: usize,
+    headers: Vec<(usize, &'v str)>,
+}
+impl<'v> mark::Visitor<'v> for Outline<'v> {
+    type Error = std::convert::Infallible;
+
+    fn visit_header(
+        &mut self,
+        lvl: usize,
+        blocks: &'v [mark::Spanned<'v>],
+    ) -> Result<(), Self::Error> {
+        if let Some(mark::Spanned {
+            block: mark::Block::Text(txt),
+            ..
+        }) = blocks.first()
+        {
+            self.headers.push((lvl, txt));
+        }
+        self.visit_blocks(blocks)
+    }
+
+    fn visit_text(&mut self, txt: &'v str) -> Result<(), Self::Error> {
+        self.words += txt.split_whitespace().count();
+        Ok(())
+    }
+}
+
+#[test]
+pub fn visitor() {
+    use mark::Visitor;
+
+    let doc = mark::to_ast("# Title\n\nSome *em* text\n\n## Sub heading\n\n- a [link](x)\n");
+    let mut outline = Outline::default();
+    outline.visit_doc(&doc).unwrap();
+    assert_eq!(outline.words, 8);
+    assert_eq!(outline.headers, vec![(1, "Title"), (2, "Sub heading")]);
+}
+
+/// Points links to the `https` version of their `http` destination.
+struct Https;
+impl<'a> mark::VisitorMut<'a> for Https {
+    fn visit_link_mut(
+        &mut self,
+        href: &mut std::borrow::Cow<'a, str>,
+        _title: &mut Option<std::borrow::Cow<'a, str>>,
+        blocks: &mut Vec<mark::Spanned<'a>>,
+    ) {
+        if let Some(rest) = href.strip_prefix("http:") {
+            *href = format!("https:{}", rest).into();
+        }
+        self.visit_blocks_mut(blocks);
+    }
+}
+
+#[test]
+pub fn visitor_mut() {
+    use mark::VisitorMut;
+
+    let mut doc = mark::to_ast("> [a](http://a.com) <http://b.com>\n");
+    Https.visit_doc_mut(&mut doc);
+    assert_eq!(
+        doc.to_string(),
+        "<blockquote>\n<p><a href=\"https://a.com\">a</a> <a href=\"https://b.com\">http://b.com</a></p>\n</blockquote>\n"
+    );
+}
+
+#[test]
+pub fn html_options() {
+    let opts = mark::Options {
+        xhtml: false,
+        code_class_prefix: "lang-",
+        soft_break: mark::SoftBreak::LineBreak,
+        ..mark::Options::default()
+    };
+    assert_eq!(
+        mark::to_html_with("a\nb\n\n---\n\n```rust\nx\ny\n```\n\n![i](s.png)\n", &opts),
+        concat!(
+            "<p>a<br>\nb</p>\n",
+            "<hr>\n",
+            "<pre><code class=\"lang-rust\">x\ny\n</code></pre>\n",
+            "<p><img src=\"s.png\" alt=\"i\"></p>\n"
+        )
+    );
+
+    let opts = mark::Options {
+        soft_break: mark::SoftBreak::Space,
+        ..mark::Options::default()
+    };
+    assert_eq!(mark::to_html_with("a\nb\n", &opts), "<p>a b</p>\n");
+}
+
+#[test]
+pub fn image_alt() {
+    assert_eq!(
+        mark::to_html("![a & \"b\" \\< `c<d`](x.png)\n"),
+        "<p><img src=\"x.png\" alt=\"a &amp; &quot;b&quot; &lt; c&lt;d\" /></p>\n"
+    );
+}
+
+#[test]
+pub fn renderer() {
+    use mark::Renderer;
+
+    let src = "# Title\n\n- [ ] Task\n";
+    let doc = mark::to_ast(src);
+    let mut out = vec![];
+    mark::HtmlRenderer::default()
+        .render_to_io(&doc, &mut out)
+        .unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), mark::to_html(src));
+}
+
+#[test]
+pub fn markup() {
+    let src = concat!(
+        "Title\n=====\n",
+        "* one\n* two\n\n",
+        "3) three\n1) four\n",
+        "A [link][ref], 1\\. *strong* and a\\_b\\_c.\n",
+        "\\- not a list\n\n",
+        "[ref]: /url 'Title'\n",
+        "~~~\ncode <b>\n~~~\n",
+    );
+    let formatted = mark::to_markup(src);
+    assert_eq!(
+        formatted,
+        concat!(
+            "# Title\n\n",
+            "* one\n* two\n\n",
+            "3. three\n4. four\n\n",
+            "A [link](/url \"Title\"), 1. *strong* and a_b_c.\n",
+            "\\- not a list\n\n",
+            "```\ncode <b>\n```\n",
+        )
+    );
+    assert!(mark::to_ast(src).same_content(&mark::to_ast(&formatted)));
+    assert_eq!(mark::to_markup(&formatted), formatted);
+}
+
+#[test]
+pub fn same_content() {
+    let doc = mark::to_ast("x, *b*\n");
+    assert!(doc.same_content(&mark::to_ast("\n\nx\\, *b*\n")));
+    assert!(!doc.same_content(&mark::to_ast("x, _b_\n")));
+}
+
+#[test]
+pub fn text() {
+    let src = concat!(
+        "Title\n=====\n",
+        "* [x] *one*\n* two\n  more\n\n",
+        "iii. three\n",
+        "> A [link](/url) and <http://x.com> \\<b\\>\n\n",
+        "<div>html</div>\n\n",
+        "```\ncode\n```\n",
+    );
+    assert_eq!(
+        mark::to_text(src),
+        concat!(
+            "Title\n=====\n\n",
+            "\u{2022} [x] one\n\u{2022} two\n  more\n\n",
+            "iii. three\n\n",
+            "> A link (/url) and http://x.com <b>\n\n",
+            "    code\n",
+        )
+    );
+}
+
+#[test]
+pub fn ansi() {
+    use mark::{AnsiRenderer, Renderer};
+
+    let doc = mark::to_ast("## A *b*\n\n- `c`\n");
+    assert_eq!(
+        AnsiRenderer.render_to_string(&doc),
+        concat!(
+            "\x1b[1;35mA \x1b[1mb\x1b[0m\x1b[1;35m\x1b[0m\n\n",
+            "\x1b[33m\u{2022}\x1b[0m \x1b[36mc\x1b[0m\n",
+        )
+    );
+}
+
+#[test]
+pub fn outline() {
+    use mark::Heading;
+
+    let heading = |level, title: &str, id: &str, children| Heading {
+        level,
+        title: title.to_string(),
+        id: id.to_string(),
+        children,
+    };
+    assert_eq!(
+        mark::outline("## `a` <b>\n\n# A b\n\n### x\n\n# A-b\n\n#\n"),
+        vec![
+            heading(2, "a <b>", "a-b", vec![]),
+            heading(1, "A b", "a-b-1", vec![heading(3, "x", "x", vec![])]),
+            heading(1, "A-b", "a-b-2", vec![]),
+            heading(1, "", "section", vec![]),
+        ]
+    );
+}
+
+#[test]
+pub fn header_ids() {
+    let options = mark::Options {
+        header_ids: true,
+        ..mark::Options::default()
+    };
+    assert_eq!(
+        mark::to_html_with("# A\n\n# A\n", &options),
+        "<h1 id=\"a\">A</h1>\n<h1 id=\"a-1\">A</h1>\n"
+    );
+    assert_eq!(mark::to_html("# A\n"), "<h1>A</h1>\n");
+}
+
+#[test]
+pub fn front_matter() {
+    use mark::{FrontMatterFormat, Value};
+
+    let doc = mark::to_ast(concat!(
+        "---\n",
+        "title: Note\n",
+        "tags: [a, b]\n",
+        "created: 2021-03-04\n",
+        "count: 2\n",
+        "---\n",
+        "- [ ] task\n",
+    ));
+    let front_matter = doc.front_matter.as_ref().unwrap();
+    assert_eq!(front_matter.format, FrontMatterFormat::Yaml);
+    assert_eq!(front_matter.title(), Some("Note"));
+    assert_eq!(front_matter.tags(), vec!["a", "b"]);
+    assert_eq!(front_matter.created(), Some("2021-03-04"));
+    assert_eq!(front_matter.get("count"), Some(&Value::Integer(2)));
+    assert_eq!(doc.tasks()[0].line, 7);
+    assert_eq!(
+        doc.to_html(&mark::Options::default()),
+        "<ul>\n<li class=\"task\"><input type=\"checkbox\" data-line=\"7\" />\n<p>task</p>\n</li>\n</ul>\n"
+    );
+
+    let doc = mark::to_ast("+++\ntitle = \"Note\"\ntags = \"a, b\"\naliases = [\"n\"]\n+++\n");
+    let front_matter = doc.front_matter.as_ref().unwrap();
+    assert_eq!(front_matter.format, FrontMatterFormat::Toml);
+    assert_eq!(front_matter.title(), Some("Note"));
+    assert_eq!(front_matter.tags(), vec!["a", "b"]);
+    assert_eq!(front_matter.aliases(), vec!["n"]);
+    assert!(doc.blocks.is_empty());
+
+    // Without a map of keys the lines are a thematic break and a header.
+    let doc = mark::to_ast("---\nTitle\n---\n");
+    assert_eq!(doc.front_matter, None);
+    assert_eq!(
+        doc.to_html(&mark::Options::default()),
+        "<hr />\n<h2>Title</h2>\n"
+    );
+    assert_eq!(
+        mark::to_markup("***\n\na: b\n\n***\n"),
+        "***\n\na: b\n\n---\n"
+    );
+}
+
+#[test]
+pub fn render_blocks() {
+    use mark::Renderer;
+
+    let src = "# A\n\ntext\n\n# A\n";
+    let options = mark::Options {
+        header_ids: true,
+        ..mark::Options::default()
+    };
+    let renderer = mark::HtmlRenderer::new(&options);
+    let doc = mark::to_ast(src);
+    let blocks = renderer.render_blocks(&doc);
+    let lines = blocks
+        .iter()
+        .map(|(span, html)| (span.start.line, span.end.line, html.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        lines,
+        vec![
+            (1, 1, "<h1 id=\"a\">A</h1>\n"),
+            (3, 3, "<p>text</p>\n"),
+            (5, 5, "<h1 id=\"a-1\">A</h1>\n"),
+        ]
+    );
+
+    let html = blocks.into_iter().map(|(_, html)| html).collect::<String>();
+    assert_eq!(html, renderer.render_to_string(&doc));
+}
+
+#[test]
+pub fn definition_multibyte_indent() {
+    assert_eq!(
+        mark::to_html("Term\n: a\n\u{3000}x\n"),
+        "<dl>\n<dt>Term</dt>\n<dd>\n<p>a</p>\n</dd>\n</dl>\n<p>x</p>\n"
+    );
+}
+
+#[test]
+pub fn markup_control_characters() {
+    let src = "a\u{1}b\n\n- $$\n  x\u{1}\n  $$\n";
+    let formatted = mark::to_markup(src);
+    assert!(formatted.contains("a\u{1}b") && formatted.contains("x\u{1}"));
+    assert!(mark::to_ast(src).same_content(&mark::to_ast(&formatted)));
+}
@@ -809,7 +809,6 @@ fn html_blocks_160() {
 }
 
 #[test]
-#[ignore]
 fn link_reference_definitions_161() {
     compare("cm/link-reference-definitions-161")
 }
@@ -821,7 +820,6 @@ fn link_reference_definitions_162() {
 }
 
 #[test]
-#[ignore]
 fn link_reference_definitions_163() {
     compare("cm/link-reference-definitions-163")
 }
@@ -855,7 +853,6 @@ fn link_reference_definitions_168() {
 }
 
 #[test]
-#[ignore]
 fn link_reference_definitions_169() {
     compare("cm/link-reference-definitions-169")
 }
@@ -872,19 +869,16 @@ fn link_reference_definitions_171() {
 }
 
 #[test]
-#[ignore]
 fn link_reference_definitions_172() {
     compare("cm/link-reference-definitions-172")
 }
 
 #[test]
-#[ignore]
 fn link_reference_definitions_173() {
     compare("cm/link-reference-definitions-173")
 }
 
 #[test]
-#[ignore]
 fn link_reference_definitions_174() {
     compare("cm/link-reference-definitions-174")
 }
@@ -896,7 +890,6 @@ fn link_reference_definitions_175() {
 }
 
 #[test]
-#[ignore]
 fn link_reference_definitions_176() {
     compare("cm/link-reference-definitions-176")
 }
@@ -936,19 +929,16 @@ fn link_reference_definitions_182() {
 }
 
 #[test]
-#[ignore]
 fn link_reference_definitions_183() {
     compare("cm/link-reference-definitions-183")
 }
 
 #[test]
-#[ignore]
 fn link_reference_definitions_184() {
     compare("cm/link-reference-definitions-184")
 }
 
 #[test]
-#[ignore]
 fn link_reference_definitions_185() {
     compare("cm/link-reference-definitions-185")
 }
@@ -960,13 +950,11 @@ fn link_reference_definitions_186() {
 }
 
 #[test]
-#[ignore]
 fn link_reference_definitions_187() {
     compare("cm/link-reference-definitions-187")
 }
 
 #[test]
-#[ignore]
 fn link_reference_definitions_188() {
     compare("cm/link-reference-definitions-188")
 }
@@ -1478,7 +1466,6 @@ fn lists_286() {
 }
 
 #[test]
-#[ignore]
 fn lists_287() {
     compare("cm/lists-287")
 }
@@ -1538,7 +1525,6 @@ fn lists_296() {
 }
 
 #[test]
-#[ignore]
 fn inlines_297() {
     compare("cm/inlines-297")
 }
@@ -1600,13 +1586,11 @@ fn backslash_escapes_307() {
 }
 
 #[test]
-#[ignore]
 fn backslash_escapes_308() {
     compare("cm/backslash-escapes-308")
 }
 
 #[test]
-#[ignore]
 fn backslash_escapes_309() {
     compare("cm/backslash-escapes-309")
 }
@@ -1812,7 +1796,6 @@ fn code_spans_343() {
 }
 
 #[test]
-#[ignore]
 fn code_spans_344() {
     compare("cm/code-spans-344")
 }
@@ -1830,7 +1813,6 @@ fn code_spans_346() {
 }
 
 #[test]
-#[ignore]
 fn code_spans_347() {
     compare("cm/code-spans-347")
 }
@@ -1854,7 +1836,6 @@ fn emphasis_and_strong_emphasis_350() {
 }
 
 #[test]
-#[ignore]
 fn emphasis_and_strong_emphasis_351() {
     compare("cm/emphasis-and-strong-emphasis-351")
 }
@@ -1889,7 +1870,6 @@ fn emphasis_and_strong_emphasis_356() {
 }
 
 #[test]
-#[ignore]
 fn emphasis_and_strong_emphasis_357() {
     compare("cm/emphasis-and-strong-emphasis-357")
 }
@@ -1901,19 +1881,16 @@ fn emphasis_and_strong_emphasis_358() {
 }
 
 #[test]
-#[ignore]
 fn emphasis_and_strong_emphasis_359() {
     compare("cm/emphasis-and-strong-emphasis-359")
 }
 
 #[test]
-#[ignore]
 fn emphasis_and_strong_emphasis_360() {
     compare("cm/emphasis-and-strong-emphasis-360")
 }
 
 #[test]
-#[ignore]
 fn emphasis_and_strong_emphasis_361() {
     compare("cm/emphasis-and-strong-emphasis-361")
 }
@@ -2014,7 +1991,6 @@ fn emphasis_and_strong_emphasis_377() {
 }
 
 #[test]
-#[ignore]
 fn emphasis_and_strong_emphasis_378() {
     compare("cm/emphasis-and-strong-emphasis-378")
 }
@@ -2038,13 +2014,11 @@ fn emphasis_and_strong_emphasis_381() {
 }
 
 #[test]
-#[ignore]
 fn emphasis_and_strong_emphasis_382() {
     compare("cm/emphasis-and-strong-emphasis-382")
 }
 
 #[test]
-#[ignore]
 fn emphasis_and_strong_emphasis_383() {
     compare("cm/emphasis-and-strong-emphasis-383")
 }
@@ -2056,19 +2030,16 @@ fn emphasis_and_strong_emphasis_384() {
 }
 
 #[test]
-#[ignore]
 fn emphasis_and_strong_emphasis_385() {
     compare("cm/emphasis-and-strong-emphasis-385")
 }
 
 #[test]
-#[ignore]
 fn emphasis_and_strong_emphasis_386() {
     compare("cm/emphasis-and-strong-emphasis-386")
 }
 
 #[test]
-#[ignore]
 fn emphasis_and_strong_emphasis_387() {
     compare("cm/emphasis-and-strong-emphasis-387")
 }
@@ -2091,7 +2062,6 @@ fn emphasis_and_strong_emphasis_390() {
 }
 
 #[test]
-#[ignore]
 fn emphasis_and_strong_emphasis_391() {
     compare("cm/emphasis-and-strong-emphasis-391")
 }
@@ -2126,7 +2096,6 @@ fn emphasis_and_strong_emphasis_396() {
 }
 
 #[test]
-#[ignore]
 fn emphasis_and_strong_emphasis_397() {
     compare("cm/emphasis-and-strong-emphasis-397")
 }
@@ -2138,13 +2107,11 @@ fn emphasis_and_strong_emphasis_398() {
 }
 
 #[test]
-#[ignore]
 fn emphasis_and_strong_emphasis_399() {
     compare("cm/emphasis-and-strong-emphasis-399")
 }
 
 #[test]
-#[ignore]
 fn emphasis_and_strong_emphasis_400() {
     compare("cm/emphasis-and-strong-emphasis-400")
 }
@@ -2430,7 +2397,6 @@ fn emphasis_and_strong_emphasis_448() {
 }
 
 #[test]
-#[ignore]
 fn emphasis_and_strong_emphasis_449() {
     compare("cm/emphasis-and-strong-emphasis-449")
 }
@@ -2583,13 +2549,11 @@ fn emphasis_and_strong_emphasis_474() {
 }
 
 #[test]
-#[ignore]
 fn emphasis_and_strong_emphasis_475() {
     compare("cm/emphasis-and-strong-emphasis-475")
 }
 
 #[test]
-#[ignore]
 fn emphasis_and_strong_emphasis_476() {
     compare("cm/emphasis-and-strong-emphasis-476")
 }
@@ -2601,43 +2565,36 @@ fn emphasis_and_strong_emphasis_477() {
 }
 
 #[test]
-#[ignore]
 fn emphasis_and_strong_emphasis_478() {
     compare("cm/emphasis-and-strong-emphasis-478")
 }
 
 #[test]
-#[ignore]
 fn emphasis_and_strong_emphasis_479() {
     compare("cm/emphasis-and-strong-emphasis-479")
 }
 
 #[test]
-#[ignore]
 fn emphasis_and_strong_emphasis_480() {
     compare("cm/emphasis-and-strong-emphasis-480")
 }
 
 #[test]
-#[ignore]
 fn links_481() {
     compare("cm/links-481")
 }
 
 #[test]
-#[ignore]
 fn links_482() {
     compare("cm/links-482")
 }
 
 #[test]
-#[ignore]
 fn links_483() {
     compare("cm/links-483")
 }
 
 #[test]
-#[ignore]
 fn links_484() {
     compare("cm/links-484")
 }
@@ -2664,7 +2621,6 @@ fn links_488() {
 }
 
 #[test]
-#[ignore]
 fn links_489() {
     compare("cm/links-489")
 }
@@ -2682,7 +2638,6 @@ fn links_491() {
 }
 
 #[test]
-#[ignore]
 fn links_492() {
     compare("cm/links-492")
 }
@@ -2700,7 +2655,6 @@ fn links_494() {
 }
 
 #[test]
-#[ignore]
 fn links_495() {
     compare("cm/links-495")
 }
@@ -2712,7 +2666,6 @@ fn links_496() {
 }
 
 #[test]
-#[ignore]
 fn links_497() {
     compare("cm/links-497")
 }
@@ -2736,7 +2689,6 @@ fn links_500() {
 }
 
 #[test]
-#[ignore]
 fn links_501() {
     compare("cm/links-501")
 }
@@ -2760,7 +2712,6 @@ fn links_504() {
 }
 
 #[test]
-#[ignore]
 fn links_505() {
     compare("cm/links-505")
 }
@@ -2777,7 +2728,6 @@ fn links_507() {
 }
 
 #[test]
-#[ignore]
 fn links_508() {
     compare("cm/links-508")
 }
@@ -2788,13 +2738,11 @@ fn links_509() {
 }
 
 #[test]
-#[ignore]
 fn links_510() {
     compare("cm/links-510")
 }
 
 #[test]
-#[ignore]
 fn links_511() {
     compare("cm/links-511")
 }
@@ -2806,7 +2754,6 @@ fn links_512() {
 }
 
 #[test]
-#[ignore]
 fn links_513() {
     compare("cm/links-513")
 }
@@ -2865,19 +2812,16 @@ fn links_522() {
 }
 
 #[test]
-#[ignore]
 fn links_523() {
     compare("cm/links-523")
 }
 
 #[test]
-#[ignore]
 fn links_524() {
     compare("cm/links-524")
 }
 
 #[test]
-#[ignore]
 fn links_525() {
     compare("cm/links-525")
 }
@@ -2889,7 +2833,6 @@ fn links_526() {
 }
 
 #[test]
-#[ignore]
 fn links_527() {
     compare("cm/links-527")
 }
@@ -2937,13 +2880,11 @@ fn links_534() {
 }
 
 #[test]
-#[ignore]
 fn links_535() {
     compare("cm/links-535")
 }
 
 #[test]
-#[ignore]
 fn links_536() {
     compare("cm/links-536")
 }
@@ -2955,19 +2896,16 @@ fn links_537() {
 }
 
 #[test]
-#[ignore]
 fn links_538() {
     compare("cm/links-538")
 }
 
 #[test]
-#[ignore]
 fn links_539() {
     compare("cm/links-539")
 }
 
 #[test]
-#[ignore]
 fn links_540() {
     compare("cm/links-540")
 }
@@ -3000,7 +2938,6 @@ fn links_545() {
 }
 
 #[test]
-#[ignore]
 fn links_546() {
     compare("cm/links-546")
 }
@@ -3016,7 +2953,6 @@ fn links_548() {
 }
 
 #[test]
-#[ignore]
 fn links_549() {
     compare("cm/links-549")
 }
@@ -3028,19 +2964,16 @@ fn links_550() {
 }
 
 #[test]
-#[ignore]
 fn links_551() {
     compare("cm/links-551")
 }
 
 #[test]
-#[ignore]
 fn links_552() {
     compare("cm/links-552")
 }
 
 #[test]
-#[ignore]
 fn links_553() {
     compare("cm/links-553")
 }
@@ -3058,25 +2991,21 @@ fn links_555() {
 }
 
 #[test]
-#[ignore]
 fn links_556() {
     compare("cm/links-556")
 }
 
 #[test]
-#[ignore]
 fn links_557() {
     compare("cm/links-557")
 }
 
 #[test]
-#[ignore]
 fn links_558() {
     compare("cm/links-558")
 }
 
 #[test]
-#[ignore]
 fn links_559() {
     compare("cm/links-559")
 }
@@ -3088,25 +3017,21 @@ fn links_560() {
 }
 
 #[test]
-#[ignore]
 fn links_561() {
     compare("cm/links-561")
 }
 
 #[test]
-#[ignore]
 fn links_562() {
     compare("cm/links-562")
 }
 
 #[test]
-#[ignore]
 fn links_563() {
     compare("cm/links-563")
 }
 
 #[test]
-#[ignore]
 fn links_564() {
     compare("cm/links-564")
 }
@@ -3118,7 +3043,6 @@ fn links_565() {
 }
 
 #[test]
-#[ignore]
 fn links_566() {
     compare("cm/links-566")
 }
@@ -3130,109 +3054,91 @@ fn links_567() {
 }
 
 #[test]
-#[ignore]
 fn images_568() {
     compare("cm/images-568")
 }
 
 #[test]
-#[ignore]
 fn images_569() {
     compare("cm/images-569")
 }
 
 #[test]
-#[ignore]
 fn images_570() {
     compare("cm/images-570")
 }
 
 #[test]
-#[ignore]
 fn images_571() {
     compare("cm/images-571")
 }
 
 #[test]
-#[ignore]
 fn images_572() {
     compare("cm/images-572")
 }
 
 #[test]
-#[ignore]
 fn images_573() {
     compare("cm/images-573")
 }
 
 #[test]
-#[ignore]
 fn images_574() {
     compare("cm/images-574")
 }
 
 #[test]
-#[ignore]
 fn images_575() {
     compare("cm/images-575")
 }
 
 #[test]
-#[ignore]
 fn images_576() {
     compare("cm/images-576")
 }
 
 #[test]
-#[ignore]
 fn images_577() {
     compare("cm/images-577")
 }
 
 #[test]
-#[ignore]
 fn images_578() {
     compare("cm/images-578")
 }
 
 #[test]
-#[ignore]
 fn images_579() {
     compare("cm/images-579")
 }
 
 #[test]
-#[ignore]
 fn images_580() {
     compare("cm/images-580")
 }
 
 #[test]
-#[ignore]
 fn images_581() {
     compare("cm/images-581")
 }
 
 #[test]
-#[ignore]
 fn images_582() {
     compare("cm/images-582")
 }
 
 #[test]
-#[ignore]
 fn images_583() {
     compare("cm/images-583")
 }
 
 #[test]
-#[ignore]
 fn images_584() {
     compare("cm/images-584")
 }
 
 #[test]
-#[ignore]
 fn images_585() {
     compare("cm/images-585")
 }
@@ -3244,19 +3150,16 @@ fn images_586() {
 }
 
 #[test]
-#[ignore]
 fn images_587() {
     compare("cm/images-587")
 }
 
 #[test]
-#[ignore]
 fn images_588() {
     compare("cm/images-588")
 }
 
 #[test]
-#[ignore]
 fn images_589() {
     compare("cm/images-589")
 }
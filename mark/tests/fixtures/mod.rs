@@ -1,5 +1,5 @@
 use pretty_assertions::assert_eq;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::Read;
 use std::path::Path;
 
@@ -25,6 +25,20 @@ pub fn compare(name: &str) {
     assert_eq!(actual.trim_end(), result);
 }
 
+/// Formats the source of the fixture `name` and checks the formatted source
+/// parses to the same document and is already formatted.
+pub fn round_trip(name: &str) {
+    let src = fs::read_to_string(format!("tests/fixtures/{}.md", name)).unwrap();
+    let formatted = mark::to_markup(&src);
+
+    assert!(
+        mark::to_ast(&src).same_content(&mark::to_ast(&formatted)),
+        "{} changed when formatted",
+        name
+    );
+    assert_eq!(mark::to_markup(&formatted), formatted);
+}
+
 mod cm;
 
 #[test]
@@ -71,3 +85,79 @@ pub fn strong() {
 pub fn code() {
     compare("data/code")
 }
+
+#[test]
+pub fn table() {
+    compare("data/table")
+}
+
+#[test]
+pub fn task_list() {
+    compare("data/task_list")
+}
+
+#[test]
+pub fn links() {
+    compare("data/links")
+}
+
+#[test]
+pub fn note_links() {
+    compare("data/note_links")
+}
+
+#[test]
+pub fn math() {
+    compare("data/math")
+}
+
+#[test]
+pub fn footnotes() {
+    compare("data/footnotes")
+}
+
+#[test]
+pub fn definition_list() {
+    compare("data/definition_list")
+}
+
+#[test]
+pub fn inline_extensions() {
+    compare("data/inline_extensions")
+}
+
+#[test]
+pub fn front_matter() {
+    compare("data/front_matter")
+}
+
+#[test]
+pub fn toc() {
+    compare("data/toc")
+}
+
+/// Checks that formatting keeps the content of each of the fixtures in `dir`.
+fn round_trip_dir(dir: &str) {
+    let mut names: Vec<String> = fs::read_dir(format!("tests/fixtures/{}", dir))
+        .unwrap()
+        .filter_map(|entry| entry.unwrap().file_name().into_string().ok())
+        .filter_map(|file| {
+            file.strip_suffix(".md")
+                .map(|name| format!("{}/{}", dir, name))
+        })
+        .collect();
+    names.sort();
+    for name in names {
+        round_trip(&name);
+    }
+}
+
+#[test]
+pub fn formatted_round_trip() {
+    round_trip_dir("data");
+}
+
+#[test]
+pub fn formatted_round_trip_cm() {
+    round_trip_dir("cm");
+}
@@ -1,3 +1,4 @@
 extern crate mark;
 
+mod api;
 mod fixtures;
@@ -0,0 +1,12 @@
+// Renders the math blocks generated by mark with KaTeX. The editor preview
+// calls this again each time it is updated.
+function renderMath(root = document) {
+  root.querySelectorAll('.math').forEach((el) => {
+    katex.render(el.textContent, el, {
+      displayMode: el.classList.contains('display'),
+      throwOnError: false,
+    });
+  });
+}
+
+renderMath();
@@ -1,8 +1,271 @@
 //! The Archive object gives access to the files in the archive. It is used
 //! to work with the notes and assets which are stored there.
 
+use crate::fulltext::SearchIndex;
+use crate::links::list_files;
 use rocket::response::NamedFile;
-use std::path::Path;
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+use std::fs::Metadata;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::time::SystemTime;
+use tokio::fs;
+use tokio::task;
+
+/// Errors which can happen when modifying a note.
+#[derive(Debug)]
+pub enum EditError {
+    /// The note does not exist.
+    NotFound,
+    /// The note was changed since the hash provided with the edit was taken.
+    Conflict,
+    /// A new note was given the name of a note which already exists.
+    Exists,
+    /// A new note was given a name which is empty, hidden or outside of the
+    /// notes directory.
+    InvalidName,
+    /// The requested line is not a task list item.
+    NotATask,
+    /// The note could not be written.
+    Io(io::Error),
+}
+
+/// A note or a directory of notes in the archive.
+#[derive(serde::Serialize)]
+pub struct NoteEntry {
+    /// The file name of the note or directory.
+    pub name: String,
+    /// The path from the root of the notes directory.
+    pub path: String,
+    /// The title of the note, from its front matter or first header, or the
+    /// name if it has neither. The title of a directory is its name.
+    pub title: String,
+    pub is_dir: bool,
+    /// The time the note was modified, for a directory the latest time any
+    /// of its notes were modified.
+    #[serde(skip)]
+    pub modified: Option<SystemTime>,
+    /// The size of the note in bytes, for a directory the size of all of its
+    /// notes.
+    pub size: u64,
+    /// The modified time and size formatted for display.
+    pub modified_text: String,
+    pub size_text: String,
+    /// The notes and directories in a directory.
+    pub entries: Vec<NoteEntry>,
+}
+impl NoteEntry {
+    fn new(
+        name: String,
+        path: String,
+        title: String,
+        modified: Option<SystemTime>,
+        size: u64,
+    ) -> Self {
+        Self {
+            name,
+            path,
+            title,
+            is_dir: false,
+            modified,
+            size,
+            modified_text: modified.map(format_time).unwrap_or_default(),
+            size_text: format_size(size),
+            entries: vec![],
+        }
+    }
+}
+
+/// An asset which was added to the archive.
+pub struct StoredAsset {
+    /// The path of the asset from the root of the assets directory.
+    pub path: String,
+    /// The metadata kept alongside the asset.
+    pub meta: AssetMeta,
+}
+
+/// The metadata of an asset, kept in a hidden file next to the asset as the
+/// asset itself is named by the hash of its contents.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct AssetMeta {
+    /// The name of the file when it was uploaded.
+    pub name: String,
+    pub content_type: String,
+    pub size: u64,
+}
+
+/// Returns the content type and extension of the file `contents` from the
+/// signature it starts with, if it is one of the known types.
+fn sniff(contents: &[u8]) -> Option<(&'static str, &'static str)> {
+    const SIGNATURES: [(&[u8], &str, &str); 6] = [
+        (b"\x89PNG\r\n\x1a\n", "image/png", "png"),
+        (b"\xff\xd8\xff", "image/jpeg", "jpg"),
+        (b"GIF87a", "image/gif", "gif"),
+        (b"GIF89a", "image/gif", "gif"),
+        (b"%PDF-", "application/pdf", "pdf"),
+        (b"\x1a\x45\xdf\xa3", "video/webm", "webm"),
+    ];
+    if contents.len() >= 12 && &contents[..4] == b"RIFF" && &contents[8..12] == b"WEBP" {
+        return Some(("image/webp", "webp"));
+    }
+    SIGNATURES
+        .iter()
+        .find(|(signature, _, _)| contents.starts_with(signature))
+        .map(|&(_, content_type, ext)| (content_type, ext))
+}
+
+/// Returns the file extension `ext` in lower case with any characters other
+/// than letters and digits removed.
+fn clean_ext(ext: &str) -> String {
+    ext.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// The order of the entries in a list of notes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NoteSort {
+    Name,
+    Title,
+    Modified,
+    Size,
+}
+impl NoteSort {
+    /// All of the orders, along with the name used in a query.
+    pub const ALL: [(NoteSort, &'static str); 4] = [
+        (NoteSort::Name, "name"),
+        (NoteSort::Title, "title"),
+        (NoteSort::Modified, "modified"),
+        (NoteSort::Size, "size"),
+    ];
+
+    /// Returns the order with the query `name`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|(_, n)| *n == name)
+            .map(|(sort, _)| *sort)
+    }
+
+    fn compare(self, a: &NoteEntry, b: &NoteEntry) -> Ordering {
+        match self {
+            NoteSort::Name => a.name.cmp(&b.name),
+            NoteSort::Title => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+            NoteSort::Modified => a.modified.cmp(&b.modified),
+            NoteSort::Size => a.size.cmp(&b.size),
+        }
+    }
+}
+
+/// Sorts `entries`, and the entries of each directory, by `sort`. Directories
+/// are listed before notes.
+pub fn sort_notes(entries: &mut [NoteEntry], sort: NoteSort, descending: bool) {
+    entries.sort_by(|a, b| {
+        let order = sort.compare(a, b).then_with(|| a.name.cmp(&b.name));
+        let order = if descending { order.reverse() } else { order };
+        b.is_dir.cmp(&a.is_dir).then(order)
+    });
+    for entry in entries {
+        sort_notes(&mut entry.entries, sort, descending);
+    }
+}
+
+/// Formats the modified `time` of a note in the local time zone.
+fn format_time(time: SystemTime) -> String {
+    chrono::DateTime::<chrono::Local>::from(time)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+/// Formats a `size` in bytes with the largest unit which keeps it above 1.
+fn format_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if size < 1024 {
+        return format!("{} B", size);
+    }
+    #[allow(clippy::cast_precision_loss)]
+    let mut size = size as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Returns the title of the note `doc`, from its front matter or its first
+/// header.
+pub fn note_title(doc: &mark::Doc) -> Option<String> {
+    let title = doc
+        .front_matter
+        .as_ref()
+        .and_then(mark::FrontMatter::title)
+        .map(str::to_string);
+    title.or_else(|| {
+        doc.outline()
+            .into_iter()
+            .next()
+            .map(|heading| heading.title)
+    })
+}
+
+/// Determines if `path` is a relative path which stays inside of the
+/// directory it is joined to.
+fn is_contained(path: &Path) -> bool {
+    path.components().all(|c| matches!(c, Component::Normal(_)))
+}
+
+/// Determines if `file` can be used as the name of a new note. Hidden names
+/// are rejected, which also keeps notes from clashing with the temporary files
+/// used while writing.
+fn is_valid_name(file: &Path) -> bool {
+    file.file_name().is_some()
+        && file.components().all(|c| match c {
+            Component::Normal(name) => !name.to_string_lossy().starts_with('.'),
+            _ => false,
+        })
+}
+
+/// Returns the SHA-256 hash of the note `contents`. The hash is used to
+/// detect if a note has changed between being viewed and being edited.
+pub fn content_hash(contents: &str) -> String {
+    format!("{:x}", Sha256::digest(contents.as_bytes()))
+}
+
+/// Flips the task marker on the 1-based `line` of `contents` between `[ ]`
+/// and `[x]`. Returns `None` if there is no task marker on the line.
+fn toggle_task_marker(contents: &str, line: usize) -> Option<String> {
+    let start = match line {
+        0 => return None,
+        1 => 0,
+        _ => contents.match_indices('\n').nth(line - 2)?.0 + 1,
+    };
+    let end = contents[start..]
+        .find('\n')
+        .map_or(contents.len(), |e| start + e);
+
+    // The task marker is the first marker on the line as list and blockquote
+    // markers can't contain a `[`.
+    let pos = start
+        + ["[ ]", "[x]", "[X]"]
+            .iter()
+            .filter_map(|marker| contents[start..end].find(marker))
+            .min()?;
+    let mark = if contents[pos..].starts_with("[ ]") {
+        "[x]"
+    } else {
+        "[ ]"
+    };
+    Some(format!(
+        "{}{}{}",
+        &contents[..pos],
+        mark,
+        &contents[pos + 3..]
+    ))
+}
 
 /// The Archive provides access to the notes and assets in the archive.
 pub struct Archive {
@@ -34,4 +297,232 @@ impl Archive {
     pub async fn retrieve_note(&self, file: &Path) -> Option<NamedFile> {
         self.retrieve(&self.data_dir, &file).await
     }
+
+    /// Lists the notes in `dir`, and in each of its directories, with their
+    /// titles from `search`. The `dir` is the path from the root of the notes
+    /// directory. Hidden files are skipped. Returns `None` if `dir` is not a
+    /// directory in the archive.
+    pub async fn list_notes(&self, dir: &Path, search: &SearchIndex) -> Option<Vec<NoteEntry>> {
+        if !is_contained(dir) {
+            return None;
+        }
+        let root = PathBuf::from(&self.data_dir);
+        if !fs::metadata(root.join(dir)).await.ok()?.is_dir() {
+            return None;
+        }
+
+        let walk_dir = dir.to_path_buf();
+        let files = task::spawn_blocking(move || {
+            let mut files = vec![];
+            list_files(&root, &walk_dir, &mut files);
+            files
+        })
+        .await
+        .unwrap_or_default();
+        Some(note_tree(dir, &files, search))
+    }
+
+    /// Returns a new path in the assets directory for an upload to be written
+    /// to before it is added with `store_asset`.
+    pub async fn staging_path(&self) -> io::Result<PathBuf> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        fs::create_dir_all(&self.asset_dir).await?;
+        let nanos = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|time| time.as_nanos())
+            .unwrap_or_default();
+        let num = NEXT.fetch_add(1, AtomicOrdering::Relaxed);
+        Ok(Path::new(&self.asset_dir).join(format!(".upload-{}-{}", nanos, num)))
+    }
+
+    /// Adds the upload written to `staged` as an asset. The asset is named by
+    /// the SHA-256 hash of its contents, so uploading the same file again
+    /// gives the same asset. The `name` and `content_type` of the upload are
+    /// kept as the metadata of the asset. If `sniff` is set the content type
+    /// is taken from the contents of the file when it is a known type.
+    pub async fn store_asset(
+        &self,
+        staged: &Path,
+        name: &str,
+        content_type: &str,
+        sniff_type: bool,
+    ) -> io::Result<StoredAsset> {
+        let contents = fs::read(staged).await?;
+        let hash = format!("{:x}", Sha256::digest(&contents));
+
+        let (content_type, ext) = match sniff(&contents).filter(|_| sniff_type) {
+            Some((content_type, ext)) => (content_type.to_string(), ext.to_string()),
+            None => {
+                let ext = Path::new(name)
+                    .extension()
+                    .map(|ext| clean_ext(&ext.to_string_lossy()))
+                    .unwrap_or_default();
+                (content_type.to_string(), ext)
+            }
+        };
+        let file = if ext.is_empty() {
+            hash.clone()
+        } else {
+            format!("{}.{}", hash, ext)
+        };
+        let path = format!("{}/{}", &hash[..2], file);
+        let meta = AssetMeta {
+            name: name.to_string(),
+            content_type,
+            size: contents.len() as u64,
+        };
+
+        let dest = Path::new(&self.asset_dir).join(&path);
+        if fs::metadata(&dest).await.is_ok() {
+            fs::remove_file(staged).await?;
+            return Ok(StoredAsset { path, meta });
+        }
+        let dir = Path::new(&self.asset_dir).join(&hash[..2]);
+        fs::create_dir_all(&dir).await?;
+        let meta_file = toml::to_string(&meta)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        fs::write(dir.join(format!(".{}.toml", file)), meta_file).await?;
+        fs::rename(staged, &dest).await?;
+        Ok(StoredAsset { path, meta })
+    }
+
+    /// Read the contents of the note at `file`. The `file` is the file path
+    /// from the root of the notes directory.
+    pub async fn read_note(&self, file: &Path) -> Option<String> {
+        fs::read_to_string(Path::new(&self.data_dir).join(file))
+            .await
+            .ok()
+    }
+
+    /// Toggle the task on the 1-based `line` of the note at `file`. The `hash`
+    /// is the `content_hash` of the note the edit was made against, if the
+    /// note has changed since then the edit is rejected. Returns the new
+    /// contents of the note.
+    pub async fn toggle_task(
+        &self,
+        file: &Path,
+        line: usize,
+        hash: &str,
+    ) -> Result<String, EditError> {
+        let contents = self.read_note(file).await.ok_or(EditError::NotFound)?;
+        if content_hash(&contents) != hash {
+            return Err(EditError::Conflict);
+        }
+        if !mark::tasks(&contents).iter().any(|task| task.line == line) {
+            return Err(EditError::NotATask);
+        }
+
+        let contents = toggle_task_marker(&contents, line).ok_or(EditError::NotATask)?;
+        self.write_note(file, &contents)
+            .await
+            .map_err(EditError::Io)?;
+        Ok(contents)
+    }
+
+    /// Create the note at `file` with `contents`. Any missing directories are
+    /// created, an existing note is never replaced.
+    pub async fn create_note(&self, file: &Path, contents: &str) -> Result<(), EditError> {
+        if !is_valid_name(file) {
+            return Err(EditError::InvalidName);
+        }
+        if fs::metadata(Path::new(&self.data_dir).join(file))
+            .await
+            .is_ok()
+        {
+            return Err(EditError::Exists);
+        }
+        self.write_note(file, contents).await.map_err(EditError::Io)
+    }
+
+    /// Replace the contents of the note at `file`. The `hash` is the
+    /// `content_hash` of the note the edit was made against, if the note has
+    /// changed since then the edit is rejected.
+    pub async fn update_note(
+        &self,
+        file: &Path,
+        contents: &str,
+        hash: &str,
+    ) -> Result<(), EditError> {
+        let current = self.read_note(file).await.ok_or(EditError::NotFound)?;
+        if content_hash(&current) != hash {
+            return Err(EditError::Conflict);
+        }
+        self.write_note(file, contents).await.map_err(EditError::Io)
+    }
+
+    /// Write `contents` to the note at `file`. The contents are written to a
+    /// temporary file which is then renamed over the note, so a reader never
+    /// sees a partly written note.
+    async fn write_note(&self, file: &Path, contents: &str) -> io::Result<()> {
+        let path = Path::new(&self.data_dir).join(file);
+        let name = path
+            .file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "missing file name"))?;
+        let tmp = path.with_file_name(format!(".{}.tmp", name.to_string_lossy()));
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).await?;
+        }
+
+        fs::write(&tmp, contents).await?;
+        if let Err(err) = fs::rename(&tmp, &path).await {
+            let _ = fs::remove_file(&tmp).await;
+            return Err(err);
+        }
+        Ok(())
+    }
+}
+
+/// Arranges the `files` under `dir` into a tree of entries. The titles of
+/// the notes are taken from the search index.
+fn note_tree(dir: &Path, files: &[(String, Metadata)], search: &SearchIndex) -> Vec<NoteEntry> {
+    let mut entries = vec![];
+    let files = files
+        .iter()
+        .filter_map(|(file, meta)| Some((Path::new(file).strip_prefix(dir).ok()?, meta)));
+    for (rel, meta) in files {
+        let mut path = dir.to_path_buf();
+        let mut level = &mut entries;
+        let mut components = rel.iter().peekable();
+        while let Some(component) = components.next() {
+            let name = component.to_string_lossy().to_string();
+            path.push(&name);
+            let path_str = path.to_string_lossy().to_string();
+            if components.peek().is_none() {
+                let title = search.title(&path_str).unwrap_or(&name).to_string();
+                level.push(NoteEntry::new(
+                    name,
+                    path_str,
+                    title,
+                    meta.modified().ok(),
+                    meta.len(),
+                ));
+                break;
+            }
+
+            let existing = level.iter().position(|e| e.is_dir && e.name == name);
+            let idx = existing.unwrap_or_else(|| {
+                let mut entry = NoteEntry::new(name.clone(), path_str, name, None, 0);
+                entry.is_dir = true;
+                level.push(entry);
+                level.len() - 1
+            });
+            level = &mut level[idx].entries;
+        }
+    }
+    total_dirs(&mut entries);
+    entries
+}
+
+/// Sets the modified time of each directory in `entries` to the latest of
+/// its notes, and its size to their total.
+fn total_dirs(entries: &mut [NoteEntry]) {
+    for entry in entries.iter_mut().filter(|e| e.is_dir) {
+        total_dirs(&mut entry.entries);
+        let modified = entry.entries.iter().filter_map(|e| e.modified).max();
+        let size = entry.entries.iter().map(|e| e.size).sum();
+        entry.modified = modified;
+        entry.size = size;
+        entry.modified_text = modified.map(format_time).unwrap_or_default();
+        entry.size_text = format_size(size);
+    }
 }
@@ -0,0 +1,520 @@
+//! The full text search index is an inverted index over the plain text of each note in
+//! the archive, mapping each word to the notes it appears in and its
+//! positions there. The index is saved to disk so only the notes which
+//! changed since it was saved are read on startup. The plain text of each
+//! note is kept in the index for building the snippets shown in results.
+//!
+//! A query is made of words which must all appear in a note. A word ending
+//! in `*` matches any word starting with it, and words in double quotes must
+//! appear together as a phrase. The results can be filtered with `tag:name`,
+//! for notes with the tag in their front matter, and `path:dir/` for notes
+//! whose path starts with `dir/`.
+
+use crate::archive::note_title;
+use crate::links::list_files;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tokio::task;
+
+/// The version of the saved index, an index saved by another version is
+/// rebuilt.
+const INDEX_VERSION: u32 = 2;
+
+/// The number of words shown either side of the first match in a snippet.
+const SNIPPET_WORDS: usize = 12;
+
+/// The plain text of a note, along with what is needed to filter and show it
+/// in results. Lines of the text without any words, such as the rules under
+/// headers, are left out.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct IndexedNote {
+    modified: Option<SystemTime>,
+    title: String,
+    tags: Vec<String>,
+    text: String,
+}
+
+/// The index as it is saved to disk. The inverted index is built from the
+/// words of each note when it is loaded.
+#[derive(serde::Deserialize)]
+struct SavedIndex {
+    version: u32,
+    notes: HashMap<String, IndexedNote>,
+}
+
+/// A note which matched a query.
+#[derive(serde::Serialize)]
+pub struct SearchResult {
+    pub name: String,
+    pub title: String,
+    pub tags: Vec<String>,
+    pub score: f64,
+    /// The text around the first match in the note as HTML, with the
+    /// matching words in `<mark>` elements.
+    pub snippet: String,
+}
+
+/// A part of a query, each part must match for a note to be in the results.
+#[derive(Debug, PartialEq)]
+enum Clause {
+    Word(String),
+    Prefix(String),
+    Phrase(Vec<String>),
+    Tag(String),
+    Path(String),
+}
+impl Clause {
+    /// Determines if the clause matches the `word` of a note, for
+    /// highlighting the word in a snippet.
+    fn highlights(&self, word: &str) -> bool {
+        match self {
+            Clause::Word(w) => w == word,
+            Clause::Prefix(prefix) => word.starts_with(prefix.as_str()),
+            Clause::Phrase(words) => words.iter().any(|w| w == word),
+            Clause::Tag(_) | Clause::Path(_) => false,
+        }
+    }
+}
+
+/// Returns the lower case words of `text` along with the byte range of each
+/// word in `text`.
+fn words(text: &str) -> Vec<(usize, usize, String)> {
+    let mut words = vec![];
+    let mut start = None;
+    for (pos, c) in text.char_indices().chain(Some((text.len(), ' '))) {
+        match (start, c.is_alphanumeric()) {
+            (None, true) => start = Some(pos),
+            (Some(s), false) => {
+                words.push((s, pos, text[s..pos].to_lowercase()));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    words
+}
+
+/// Splits `query` into its clauses.
+fn parse_query(query: &str) -> Vec<Clause> {
+    let mut clauses = vec![];
+    let mut rest = query.trim_start();
+    while !rest.is_empty() {
+        let (part, quoted) = if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            rest = quoted.get(end + 1..).unwrap_or("");
+            (&quoted[..end], true)
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let part = &rest[..end];
+            rest = &rest[end..];
+            (part, false)
+        };
+        rest = rest.trim_start();
+
+        if !quoted {
+            if let Some(tag) = part.strip_prefix("tag:") {
+                clauses.push(Clause::Tag(tag.to_lowercase()));
+                continue;
+            }
+            if let Some(path) = part.strip_prefix("path:") {
+                clauses.push(Clause::Path(path.trim_start_matches('/').to_string()));
+                continue;
+            }
+        }
+
+        let mut part_words: Vec<String> = words(part).into_iter().map(|(_, _, w)| w).collect();
+        match part_words.len() {
+            0 => {}
+            1 if !quoted && part.ends_with('*') => {
+                clauses.push(Clause::Prefix(part_words.remove(0)));
+            }
+            1 => clauses.push(Clause::Word(part_words.remove(0))),
+            _ => clauses.push(Clause::Phrase(part_words)),
+        }
+    }
+    clauses
+}
+
+/// An index of the words in the notes of the archive.
+pub struct SearchIndex {
+    data_dir: String,
+    index_path: PathBuf,
+    notes: HashMap<String, IndexedNote>,
+    /// The notes each word appears in, with the positions of the word in the
+    /// note.
+    words: BTreeMap<String, HashMap<String, Vec<usize>>>,
+    /// Set when the index has changed since it was saved.
+    changed: bool,
+}
+
+impl SearchIndex {
+    /// Create the index of the notes in `data_dir`, loading the index saved
+    /// at `index_path` and bringing it up to date. Changes made to the notes
+    /// outside of the server are picked up here, later changes are indexed
+    /// with `update` as they are written. The index isn't saved until `save`
+    /// is called.
+    pub fn new(data_dir: &str, index_path: &str) -> Self {
+        let mut index = Self {
+            data_dir: data_dir.to_string(),
+            index_path: PathBuf::from(index_path),
+            notes: HashMap::new(),
+            words: BTreeMap::new(),
+            changed: false,
+        };
+        index.load();
+        index.refresh();
+        index
+    }
+
+    /// Load the index saved on disk. A missing or unreadable index is left
+    /// empty, to be rebuilt by `refresh`.
+    fn load(&mut self) {
+        let saved = fs::read(&self.index_path)
+            .ok()
+            .and_then(|buf| serde_json::from_slice::<SavedIndex>(&buf).ok())
+            .filter(|saved| saved.version == INDEX_VERSION);
+        if let Some(saved) = saved {
+            for (name, note) in saved.notes {
+                self.insert(name, note);
+            }
+        }
+    }
+
+    /// Save the index to disk if it changed since it was last saved. The
+    /// index is written on a blocking thread, to a temporary file which is
+    /// renamed over the old index, so a failed save never leaves a partly
+    /// written index.
+    pub async fn save(&mut self) -> io::Result<()> {
+        #[derive(serde::Serialize)]
+        struct SavedIndexRef<'a> {
+            version: u32,
+            notes: &'a HashMap<String, IndexedNote>,
+        }
+
+        if !self.changed {
+            return Ok(());
+        }
+        let buf = serde_json::to_vec(&SavedIndexRef {
+            version: INDEX_VERSION,
+            notes: &self.notes,
+        })?;
+        let path = self.index_path.clone();
+        task::spawn_blocking(move || {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            let tmp = path.with_extension("tmp");
+            fs::write(&tmp, buf)?;
+            fs::rename(&tmp, &path)
+        })
+        .await
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, err))??;
+        self.changed = false;
+        Ok(())
+    }
+
+    /// Bring the index up to date with the notes in the data directory. Only
+    /// notes which changed since they were last indexed are read again, and
+    /// notes which were removed are dropped from the index.
+    fn refresh(&mut self) {
+        let data_dir = PathBuf::from(&self.data_dir);
+        let mut files = vec![];
+        list_files(&data_dir, Path::new(""), &mut files);
+
+        let names: HashSet<&String> = files.iter().map(|(name, _)| name).collect();
+        let removed: Vec<String> = self
+            .notes
+            .keys()
+            .filter(|name| !names.contains(name))
+            .cloned()
+            .collect();
+        for name in &removed {
+            self.remove(name);
+            self.changed = true;
+        }
+
+        for (name, meta) in &files {
+            let modified = meta.modified().ok();
+            if let Some(note) = self.notes.get(name) {
+                if note.modified.is_some() && note.modified == modified {
+                    continue;
+                }
+            }
+            if let Ok(contents) = fs::read_to_string(data_dir.join(name)) {
+                self.update(name, &contents, modified);
+            }
+        }
+    }
+
+    /// Returns the title of the note `name`, if it is in the index.
+    pub fn title(&self, name: &str) -> Option<&str> {
+        self.notes.get(name).map(|note| note.title.as_str())
+    }
+
+    /// Index the note `name` which has `contents`. The `modified` time is the
+    /// time the note was last changed, if it is not known the note will be
+    /// read again on the next refresh.
+    pub fn update(&mut self, name: &str, contents: &str, modified: Option<SystemTime>) {
+        let doc = mark::to_ast(contents);
+        let tags = doc
+            .front_matter
+            .as_ref()
+            .map(|front_matter| {
+                front_matter
+                    .tags()
+                    .into_iter()
+                    .map(str::to_lowercase)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let note = IndexedNote {
+            modified,
+            title: note_title(&doc).unwrap_or_else(|| name.to_string()),
+            tags,
+            text: doc
+                .to_text()
+                .lines()
+                .filter(|line| line.chars().any(char::is_alphanumeric))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        };
+        self.remove(name);
+        self.insert(name.to_string(), note);
+        self.changed = true;
+    }
+
+    fn insert(&mut self, name: String, note: IndexedNote) {
+        for (pos, (_, _, word)) in words(&note.text).into_iter().enumerate() {
+            self.words
+                .entry(word)
+                .or_default()
+                .entry(name.clone())
+                .or_default()
+                .push(pos);
+        }
+        self.notes.insert(name, note);
+    }
+
+    fn remove(&mut self, name: &str) {
+        let text = self
+            .notes
+            .remove(name)
+            .map(|note| note.text)
+            .unwrap_or_default();
+        for (_, _, word) in words(&text) {
+            if let Some(notes) = self.words.get_mut(&word) {
+                notes.remove(name);
+                if notes.is_empty() {
+                    self.words.remove(&word);
+                }
+            }
+        }
+    }
+
+    /// Returns the notes which match `query`, best match first, up to
+    /// `limit` results.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<SearchResult> {
+        let clauses = parse_query(query);
+        if clauses.is_empty() {
+            return vec![];
+        }
+
+        // Only the notes in the posting lists of every word of the query can
+        // match, a query of only filters has to check every note.
+        let postings = clauses.iter().filter_map(|c| self.candidates(c)).collect();
+        let candidates = intersect(postings).unwrap_or_else(|| self.notes.keys().collect());
+
+        let mut results: Vec<(&String, f64)> = candidates
+            .into_iter()
+            .filter_map(|name| {
+                let note = &self.notes[name];
+                clauses
+                    .iter()
+                    .try_fold(0.0, |score, clause| {
+                        self.score(name, note, clause).map(|s| score + s)
+                    })
+                    .map(|score| (name, score))
+            })
+            .collect();
+        results.sort_by(|a, b| {
+            b.1.partial_cmp(&a.1)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| a.0.cmp(b.0))
+        });
+
+        results
+            .into_iter()
+            .take(limit)
+            .map(|(name, score)| {
+                let note = &self.notes[name];
+                SearchResult {
+                    name: name.clone(),
+                    title: note.title.clone(),
+                    tags: note.tags.clone(),
+                    score,
+                    snippet: snippet(&note.text, &clauses),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the notes in the posting lists of the words of `clause`, which
+    /// are the only notes it can match, or `None` for a filter.
+    fn candidates(&self, clause: &Clause) -> Option<HashSet<&String>> {
+        let notes = |word: &str| -> HashSet<&String> {
+            self.words
+                .get(word)
+                .map(|notes| notes.keys().collect())
+                .unwrap_or_default()
+        };
+        match clause {
+            Clause::Word(word) => Some(notes(word)),
+            Clause::Prefix(prefix) => Some(
+                self.words
+                    .range(prefix.clone()..)
+                    .take_while(|(word, _)| word.starts_with(prefix.as_str()))
+                    .flat_map(|(_, notes)| notes.keys())
+                    .collect(),
+            ),
+            Clause::Phrase(words) => intersect(words.iter().map(|word| notes(word)).collect()),
+            Clause::Tag(_) | Clause::Path(_) => None,
+        }
+    }
+
+    /// Returns the score of `clause` for the note `name`, or `None` if the
+    /// note doesn't match. A word scores more the more often it appears in
+    /// the note and the fewer notes it appears in.
+    #[allow(clippy::cast_precision_loss)]
+    fn score(&self, name: &str, note: &IndexedNote, clause: &Clause) -> Option<f64> {
+        match clause {
+            Clause::Word(word) => self.word_score(name, word),
+            Clause::Prefix(prefix) => {
+                let score: f64 = self
+                    .words
+                    .range(prefix.clone()..)
+                    .take_while(|(word, _)| word.starts_with(prefix.as_str()))
+                    .filter_map(|(word, _)| self.word_score(name, word))
+                    .sum();
+                Some(score).filter(|&score| score > 0.0)
+            }
+            Clause::Phrase(words) => {
+                let positions = words
+                    .iter()
+                    .map(|word| self.words.get(word)?.get(name))
+                    .collect::<Option<Vec<_>>>()?;
+                let count = positions[0]
+                    .iter()
+                    .filter(|&&start| {
+                        positions[1..]
+                            .iter()
+                            .enumerate()
+                            .all(|(i, pos)| pos.binary_search(&(start + i + 1)).is_ok())
+                    })
+                    .count();
+                if count == 0 {
+                    return None;
+                }
+                let idf: f64 = words.iter().map(|word| self.idf(word)).sum();
+                Some(count as f64 * idf)
+            }
+            Clause::Tag(tag) => Some(0.0).filter(|_| note.tags.contains(tag)),
+            Clause::Path(path) => Some(0.0).filter(|_| name.starts_with(path.as_str())),
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn word_score(&self, name: &str, word: &str) -> Option<f64> {
+        let count = self.words.get(word)?.get(name)?.len();
+        Some(count as f64 * self.idf(word))
+    }
+
+    /// Returns the inverse document frequency of `word`, higher for words
+    /// which appear in fewer notes.
+    #[allow(clippy::cast_precision_loss)]
+    fn idf(&self, word: &str) -> f64 {
+        let notes = self.words.get(word).map_or(0, HashMap::len);
+        (self.notes.len() as f64 / (notes as f64 + 1.0)).ln_1p()
+    }
+}
+
+/// Returns the snippet of the indexed `text` of a note around the first word
+/// which matches one of `clauses`.
+fn snippet(text: &str, clauses: &[Clause]) -> String {
+    let words = words(text);
+    let matches: Vec<bool> = words
+        .iter()
+        .map(|(_, _, word)| clauses.iter().any(|clause| clause.highlights(word)))
+        .collect();
+    let first = matches.iter().position(|&m| m).unwrap_or(0);
+    let start = first.saturating_sub(SNIPPET_WORDS);
+    let end = (first + SNIPPET_WORDS * 2).min(words.len());
+    if start >= end {
+        return String::new();
+    }
+
+    let mut snippet = String::new();
+    if start > 0 {
+        snippet.push_str("… ");
+    }
+    let mut pos = words[start].0;
+    for (&(word_start, word_end, _), &matched) in words[start..end].iter().zip(&matches[start..end])
+    {
+        push_text(&mut snippet, &text[pos..word_start]);
+        if matched {
+            snippet.push_str("<mark>");
+            push_text(&mut snippet, &text[word_start..word_end]);
+            snippet.push_str("</mark>");
+        } else {
+            push_text(&mut snippet, &text[word_start..word_end]);
+        }
+        pos = word_end;
+    }
+    if end < words.len() {
+        snippet.push_str(" …");
+    }
+    snippet
+}
+
+/// Returns the notes which are in all of `sets`, or `None` if there are no
+/// sets. The smallest set is checked against the others.
+fn intersect(mut sets: Vec<HashSet<&String>>) -> Option<HashSet<&String>> {
+    sets.sort_by_key(HashSet::len);
+    let (first, rest) = sets.split_first()?;
+    Some(
+        first
+            .iter()
+            .filter(|name| rest.iter().all(|notes| notes.contains(*name)))
+            .copied()
+            .collect(),
+    )
+}
+
+/// Adds `text` to the HTML `out`, escaped and with runs of whitespace
+/// collapsed to a single space.
+fn push_text(out: &mut String, text: &str) {
+    let mut space = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            space = true;
+            continue;
+        }
+        if space {
+            out.push(' ');
+            space = false;
+        }
+        match c {
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '&' => out.push_str("&amp;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+    if space {
+        out.push(' ');
+    }
+}
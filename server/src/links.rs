@@ -0,0 +1,123 @@
+//! The link index records the `[[note]]` links made by each note in the
+//! archive, along with the aliases given in the front matter of the note. It
+//! is used to find the notes which link to a given note.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The outgoing links and aliases of a note.
+struct NoteLinks {
+    targets: Vec<String>,
+    aliases: Vec<String>,
+}
+
+/// An index of the note links in the archive.
+pub struct LinkIndex {
+    notes: HashMap<String, NoteLinks>,
+}
+
+impl LinkIndex {
+    /// Create a new index of all of the notes in `data_dir`. Notes written
+    /// by the server after this are recorded with `update`.
+    pub fn new(data_dir: &str) -> Self {
+        let mut index = Self {
+            notes: HashMap::new(),
+        };
+        let data_dir = PathBuf::from(data_dir);
+        let mut files = vec![];
+        list_files(&data_dir, Path::new(""), &mut files);
+        for (name, _) in &files {
+            if let Ok(contents) = fs::read_to_string(data_dir.join(name)) {
+                index.update(name, &contents);
+            }
+        }
+        index
+    }
+
+    /// Record the links of the note `name` which has `contents`, replacing
+    /// any links recorded for it before.
+    pub fn update(&mut self, name: &str, contents: &str) {
+        let doc = mark::to_ast(contents);
+        let targets = doc
+            .note_links()
+            .iter()
+            .map(|target| target.trim_start_matches('/').to_string())
+            .collect();
+        let aliases = doc
+            .front_matter
+            .as_ref()
+            .map(|front_matter| {
+                front_matter
+                    .aliases()
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.notes
+            .insert(name.to_string(), NoteLinks { targets, aliases });
+    }
+
+    /// Returns the names of the notes which link to the note `name`, sorted
+    /// by name. A link target may leave off the `.md` extension of the note,
+    /// or be one of the aliases of the note.
+    pub fn backlinks(&self, name: &str) -> Vec<String> {
+        let aliases = self
+            .notes
+            .get(name)
+            .map_or(&[][..], |links| links.aliases.as_slice());
+        let mut backlinks: Vec<String> = self
+            .notes
+            .iter()
+            .filter(|(_, links)| {
+                links.targets.iter().any(|target| {
+                    target == name || format!("{}.md", target) == name || aliases.contains(target)
+                })
+            })
+            .map(|(note, _)| note.clone())
+            .collect();
+        backlinks.sort();
+        backlinks
+    }
+
+    /// Finds the note for the `[[target]]` of a note link. The target may
+    /// leave off the `.md` extension of the note.
+    fn find_note(&self, target: &str) -> Option<String> {
+        let target = target.trim_start_matches('/');
+        let with_ext = format!("{}.md", target);
+        [target, with_ext.as_str()]
+            .iter()
+            .find(|name| self.notes.contains_key(**name))
+            .map(|name| (*name).to_string())
+    }
+}
+
+impl mark::Resolver for LinkIndex {
+    fn href(&self, target: &str) -> String {
+        let note = self.find_note(target);
+        format!("/note/{}", note.as_deref().unwrap_or(target))
+    }
+
+    fn exists(&self, target: &str) -> bool {
+        self.find_note(target).is_some()
+    }
+}
+
+/// Adds the files under `root`/`dir` to `files` along with their metadata.
+/// The names are relative to `root`, hidden files are skipped.
+pub fn list_files(root: &Path, dir: &Path, files: &mut Vec<(String, fs::Metadata)>) {
+    for entry in fs::read_dir(root.join(dir)).into_iter().flatten().flatten() {
+        let name = entry.file_name();
+        if name.to_string_lossy().starts_with('.') {
+            continue;
+        }
+
+        let path = dir.join(&name);
+        match entry.metadata() {
+            Ok(meta) if meta.is_dir() => list_files(root, &path, files),
+            Ok(meta) => files.push((path.to_string_lossy().to_string(), meta)),
+            Err(_) => {}
+        }
+    }
+}
@@ -4,18 +4,26 @@
 #![allow(clippy::let_underscore_drop)]
 
 mod archive;
+mod fulltext;
+mod links;
 
-use archive::Archive;
-use rocket::http::ContentType;
-use rocket::response::status::NotFound;
+use archive::{content_hash, sort_notes, Archive, EditError, NoteEntry, NoteSort};
+use fulltext::{SearchIndex, SearchResult};
+use links::LinkIndex;
+use rocket::data::TempFile;
+use rocket::form::Form;
+use rocket::http::uri::Segments;
+use rocket::http::{ContentType, Status};
+use rocket::request::FromSegments;
+use rocket::response::status::{self, NotFound};
 use rocket::response::{content, NamedFile};
 use rocket::{Request, State};
+use rocket_contrib::json::Json;
 use rocket_contrib::serve::{crate_relative, StaticFiles};
 use rocket_contrib::templates::Template;
 use std::collections::HashMap;
 use std::env;
-use std::path::PathBuf;
-use tokio::io::AsyncReadExt;
+use std::path::{Path, PathBuf};
 use tokio::sync::RwLock;
 
 #[macro_use]
@@ -27,12 +35,117 @@ async fn assets(file: PathBuf, state: State<'_, ArchiveState>) -> Option<NamedFi
     archive.retrieve_asset(&file).await
 }
 
+#[derive(FromForm)]
+struct AssetUpload<'r> {
+    file: TempFile<'r>,
+}
+
+/// The response to an asset upload, with the markup to link to the asset
+/// from a note.
+#[derive(serde::Serialize)]
+struct UploadedAsset {
+    url: String,
+    name: String,
+    content_type: String,
+    size: u64,
+    markup: String,
+}
+
+#[post("/asset", data = "<upload>")]
+async fn upload_asset(
+    mut upload: Form<AssetUpload<'_>>,
+    state: State<'_, ArchiveState>,
+) -> Result<Json<UploadedAsset>, status::Custom<String>> {
+    let server_error =
+        |err: std::io::Error| status::Custom(Status::InternalServerError, err.to_string());
+    let content_type = upload
+        .file
+        .content_type()
+        .cloned()
+        .unwrap_or(ContentType::Binary);
+    let mut name = upload.file.name().unwrap_or("asset").to_string();
+    if let Some(ext) = content_type.extension() {
+        name = format!("{}.{}", name, ext);
+    }
+
+    let archive = state.archive.write().await;
+    let staged = archive.staging_path().await.map_err(server_error)?;
+    upload
+        .file
+        .persist_to(&staged)
+        .await
+        .map_err(server_error)?;
+    let asset = archive
+        .store_asset(
+            &staged,
+            &name,
+            &content_type.to_string(),
+            state.upload.sniff,
+        )
+        .await
+        .map_err(server_error)?;
+
+    let url = format!("/asset/{}", asset.path);
+    let alt = asset.meta.name.replace(|c| c == '[' || c == ']', "");
+    let markup = if asset.meta.content_type.starts_with("image/") {
+        format!("![{}]({})", alt, url)
+    } else {
+        format!("[{}]({})", alt, url)
+    };
+    Ok(Json(UploadedAsset {
+        url,
+        name: asset.meta.name,
+        content_type: asset.meta.content_type,
+        size: asset.meta.size,
+        markup,
+    }))
+}
+
 #[derive(serde::Serialize)]
 struct NoteContext<'a> {
     id: &'a str,
+    title: &'a str,
+    tags: &'a [&'a str],
+    created: Option<&'a str>,
     content: &'a str,
+    outline: &'a str,
+    hash: &'a str,
+    backlinks: &'a [String],
+    katex: bool,
     parent: &'static str,
 }
+
+fn render_note(links: &LinkIndex, settings: RenderSettings, id: &str, buf: &str) -> Template {
+    let options = settings.options(links);
+    let doc = mark::to_ast(buf);
+    let content = doc.to_html(&options);
+    let mut outline = String::new();
+    mark::HtmlRenderer::new(&options)
+        .render_outline(&doc.outline(), &mut outline)
+        .expect("writing to a string never fails");
+    let hash = content_hash(buf);
+    let backlinks = links.backlinks(id);
+    let front_matter = doc.front_matter.as_ref();
+    let tags = front_matter
+        .map(mark::FrontMatter::tags)
+        .unwrap_or_default();
+    let ctx = NoteContext {
+        id,
+        title: front_matter
+            .and_then(mark::FrontMatter::title)
+            .unwrap_or(id),
+        tags: &tags,
+        created: front_matter.and_then(mark::FrontMatter::created),
+        content: &content,
+        outline: &outline,
+        hash: &hash,
+        backlinks: &backlinks,
+        katex: settings.math == mark::MathStyle::Source,
+        parent: "layout",
+    };
+    Template::render("show", &ctx)
+}
+
 #[get("/note/<name..>", rank = 2, format = "text/html")]
 async fn note_html(
     name: PathBuf,
@@ -41,20 +154,247 @@ async fn note_html(
     let archive = state.archive.read().await;
     let id = name.to_str().unwrap().to_string();
 
-    let mut file = archive.retrieve_note(&name).await.unwrap();
-    let mut buf = String::new();
+    match archive.read_note(&name).await {
+        Some(buf) => {
+            let links = state.links.read().await;
+            Ok(render_note(&links, state.render, &id, &buf))
+        }
+        None => Err(NotFound(id)),
+    }
+}
+
+/// Returns the path before the final `segment` of `path`, if `path` ends
+/// with `segment`.
+fn strip_segment(path: &Path, segment: &str) -> Option<PathBuf> {
+    path.parent()
+        .filter(|_| path.ends_with(segment))
+        .map(Path::to_path_buf)
+}
+
+#[derive(FromForm)]
+struct TaskForm {
+    hash: String,
+}
+
+/// Toggles the task on `line` of the note `name`. Tasks and the editor have
+/// their own prefixes, so they can't be confused with the path of a note.
+#[post("/task/<line>/<name..>", data = "<form>")]
+async fn toggle_task(
+    line: usize,
+    name: PathBuf,
+    form: Form<TaskForm>,
+    state: State<'_, ArchiveState>,
+) -> Result<Template, status::Custom<String>> {
+    let archive = state.archive.write().await;
+    let id = name.to_str().unwrap().to_string();
 
-    if file.read_to_string(&mut buf).await.is_err() {
-        return Err(NotFound(name.to_str().unwrap().to_string()));
+    match archive.toggle_task(&name, line, &form.hash).await {
+        Ok(buf) => {
+            let mut links = state.links.write().await;
+            links.update(&id, &buf);
+            index_note(&state, &id, &buf).await;
+            Ok(render_note(&links, state.render, &id, &buf))
+        }
+        Err(EditError::NotATask) => Err(status::Custom(
+            Status::BadRequest,
+            format!("{}:{} is not a task", id, line),
+        )),
+        Err(err) => Err(edit_error(&id, err)),
     }
+}
 
-    let buf = mark::to_html(&buf);
-    let ctx = NoteContext {
-        id: &id,
-        content: &buf,
+/// Returns the response for an `err` when editing the note `id`.
+fn edit_error(id: &str, err: EditError) -> status::Custom<String> {
+    match err {
+        EditError::NotFound => status::Custom(Status::NotFound, id.to_string()),
+        EditError::Conflict => status::Custom(
+            Status::Conflict,
+            format!("{} has changed, reload and try again", id),
+        ),
+        EditError::Exists => status::Custom(Status::Conflict, format!("{} already exists", id)),
+        EditError::InvalidName => status::Custom(
+            Status::BadRequest,
+            format!("{} is not a valid note name", id),
+        ),
+        EditError::NotATask => status::Custom(Status::BadRequest, format!("{} is not a task", id)),
+        EditError::Io(err) => status::Custom(Status::InternalServerError, err.to_string()),
+    }
+}
+
+#[derive(serde::Serialize)]
+struct EditorContext<'a> {
+    id: &'a str,
+    title: &'a str,
+    contents: &'a str,
+    hash: &'a str,
+    is_new: bool,
+    katex: bool,
+    parent: &'static str,
+}
+
+#[get("/new")]
+async fn new_note(state: State<'_, ArchiveState>) -> Template {
+    let ctx = EditorContext {
+        id: "",
+        title: "New note",
+        contents: "",
+        hash: "",
+        is_new: true,
+        katex: state.render.math == mark::MathStyle::Source,
         parent: "layout",
     };
-    Ok(Template::render("show", &ctx))
+    Template::render("edit", &ctx)
+}
+
+#[get("/edit/<name..>")]
+async fn edit_note(
+    name: PathBuf,
+    state: State<'_, ArchiveState>,
+) -> Result<Template, NotFound<String>> {
+    let archive = state.archive.read().await;
+    let id = name.to_str().unwrap();
+
+    match archive.read_note(&name).await {
+        Some(buf) => {
+            let hash = content_hash(&buf);
+            let ctx = EditorContext {
+                id,
+                title: id,
+                contents: &buf,
+                hash: &hash,
+                is_new: false,
+                katex: state.render.math == mark::MathStyle::Source,
+                parent: "layout",
+            };
+            Ok(Template::render("edit", &ctx))
+        }
+        None => Err(NotFound(id.to_string())),
+    }
+}
+
+#[derive(FromForm)]
+struct NewNoteForm {
+    contents: String,
+}
+
+#[post("/note/<name..>", rank = 2, data = "<form>")]
+async fn create_note(
+    name: PathBuf,
+    form: Form<NewNoteForm>,
+    state: State<'_, ArchiveState>,
+) -> Result<Template, status::Custom<String>> {
+    let archive = state.archive.write().await;
+    let id = name.to_str().unwrap().to_string();
+
+    match archive.create_note(&name, &form.contents).await {
+        Ok(()) => {
+            let mut links = state.links.write().await;
+            links.update(&id, &form.contents);
+            index_note(&state, &id, &form.contents).await;
+            Ok(render_note(&links, state.render, &id, &form.contents))
+        }
+        Err(err) => Err(edit_error(&id, err)),
+    }
+}
+
+#[derive(FromForm)]
+struct NoteForm {
+    contents: String,
+    hash: String,
+}
+
+#[put("/note/<name..>", data = "<form>")]
+async fn update_note(
+    name: PathBuf,
+    form: Form<NoteForm>,
+    state: State<'_, ArchiveState>,
+) -> Result<Template, status::Custom<String>> {
+    let archive = state.archive.write().await;
+    let id = name.to_str().unwrap().to_string();
+
+    match archive.update_note(&name, &form.contents, &form.hash).await {
+        Ok(()) => {
+            let mut links = state.links.write().await;
+            links.update(&id, &form.contents);
+            index_note(&state, &id, &form.contents).await;
+            Ok(render_note(&links, state.render, &id, &form.contents))
+        }
+        Err(err) => Err(edit_error(&id, err)),
+    }
+}
+
+/// The path of the backlinks of a note, `<note..>/backlinks`.
+struct BacklinksPath {
+    note: PathBuf,
+}
+impl<'r> FromSegments<'r> for BacklinksPath {
+    type Error = &'static str;
+
+    fn from_segments(segments: Segments<'r>) -> Result<Self, Self::Error> {
+        let path = PathBuf::from_segments(segments).map_err(|_| "invalid path")?;
+        let note = strip_segment(&path, "backlinks").ok_or("missing backlinks")?;
+        Ok(Self { note })
+    }
+}
+
+#[get("/api/note/<backlinks..>", rank = 1)]
+async fn note_backlinks(
+    backlinks: BacklinksPath,
+    state: State<'_, ArchiveState>,
+) -> Json<Vec<String>> {
+    let links = state.links.read().await;
+    Json(links.backlinks(backlinks.note.to_str().unwrap()))
+}
+
+/// The path of the parsed tree of a note, `<note..>.json`.
+struct AstPath {
+    note: PathBuf,
+}
+impl<'r> FromSegments<'r> for AstPath {
+    type Error = &'static str;
+
+    fn from_segments(segments: Segments<'r>) -> Result<Self, Self::Error> {
+        let path = PathBuf::from_segments(segments).map_err(|_| "invalid path")?;
+        if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            return Err("missing .json extension");
+        }
+        Ok(Self {
+            note: path.with_extension(""),
+        })
+    }
+}
+
+#[get("/api/note/<ast..>", rank = 2)]
+async fn note_ast(
+    ast: AstPath,
+    state: State<'_, ArchiveState>,
+) -> Result<Json<mark::Doc<'static>>, NotFound<String>> {
+    let archive = state.archive.read().await;
+    match archive.read_note(&ast.note).await {
+        Some(buf) => Ok(Json(mark::to_ast(&buf).into_owned())),
+        None => Err(NotFound(ast.note.to_str().unwrap().to_string())),
+    }
+}
+
+/// A top level block of a note rendered for the editor preview, along with
+/// the span of the source it came from.
+#[derive(serde::Serialize)]
+struct PreviewBlock {
+    span: mark::Span,
+    html: String,
+}
+
+#[post("/api/render", data = "<source>")]
+async fn render_preview(source: String, state: State<'_, ArchiveState>) -> Json<Vec<PreviewBlock>> {
+    let links = state.links.read().await;
+    let options = state.render.options(&links);
+    let doc = mark::to_ast(&source);
+    let blocks = mark::HtmlRenderer::new(&options)
+        .render_blocks(&doc)
+        .into_iter()
+        .map(|(span, html)| PreviewBlock { span, html })
+        .collect();
+    Json(blocks)
 }
 
 #[get("/note/<name..>", rank = 1, format = "text/plain")]
@@ -66,6 +406,131 @@ async fn note_plain(name: PathBuf, state: State<'_, ArchiveState>) -> content::C
     )
 }
 
+/// A link which sorts the notes list by a column.
+#[derive(serde::Serialize)]
+struct SortLink {
+    name: &'static str,
+    href: String,
+    active: bool,
+}
+
+#[derive(serde::Serialize)]
+struct NotesContext<'a> {
+    dir: &'a str,
+    entries: &'a [NoteEntry],
+    sort_links: &'a [SortLink],
+    descending: bool,
+    parent: &'static str,
+}
+
+async fn render_notes(
+    state: &ArchiveState,
+    dir: &Path,
+    sort: Option<&str>,
+    order: Option<&str>,
+) -> Option<Template> {
+    let archive = state.archive.read().await;
+    let search = state.search.read().await;
+    let mut entries = archive.list_notes(dir, &search).await?;
+    let sort = sort.and_then(NoteSort::from_name).unwrap_or(NoteSort::Name);
+    let descending = order == Some("desc");
+    sort_notes(&mut entries, sort, descending);
+
+    // Choosing the current sort again flips the order.
+    let sort_links = NoteSort::ALL
+        .iter()
+        .map(|&(link, name)| {
+            let active = link == sort;
+            let order = if active && !descending { "desc" } else { "asc" };
+            SortLink {
+                name,
+                href: format!("?sort={}&order={}", name, order),
+                active,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let ctx = NotesContext {
+        dir: dir.to_str().unwrap(),
+        entries: &entries,
+        sort_links: &sort_links,
+        descending,
+        parent: "layout",
+    };
+    Some(Template::render("notes", &ctx))
+}
+
+#[get("/notes?<sort>&<order>")]
+async fn notes(
+    sort: Option<String>,
+    order: Option<String>,
+    state: State<'_, ArchiveState>,
+) -> Result<Template, NotFound<String>> {
+    render_notes(&state, Path::new(""), sort.as_deref(), order.as_deref())
+        .await
+        .ok_or_else(|| NotFound(String::new()))
+}
+
+#[get("/notes/<dir..>?<sort>&<order>", rank = 2)]
+async fn notes_dir(
+    dir: PathBuf,
+    sort: Option<String>,
+    order: Option<String>,
+    state: State<'_, ArchiveState>,
+) -> Result<Template, NotFound<String>> {
+    render_notes(&state, &dir, sort.as_deref(), order.as_deref())
+        .await
+        .ok_or_else(|| NotFound(dir.to_str().unwrap().to_string()))
+}
+
+/// Indexes the note `id`, just written with `contents`, for search and saves
+/// the index. A failed save is logged, the note is still searchable until the
+/// server restarts.
+async fn index_note(state: &ArchiveState, id: &str, contents: &str) {
+    let mut search = state.search.write().await;
+    search.update(id, contents, None);
+    if let Err(err) = search.save().await {
+        log::error!("failed to save the search index: {}", err);
+    }
+}
+
+/// The most results returned for a search.
+const SEARCH_LIMIT: usize = 50;
+
+#[derive(serde::Serialize)]
+struct SearchContext<'a> {
+    query: &'a str,
+    results: &'a [SearchResult],
+    parent: &'static str,
+}
+
+/// Returns the results for `query`.
+async fn run_search(state: &ArchiveState, query: &str, limit: usize) -> Vec<SearchResult> {
+    state.search.read().await.search(query, limit)
+}
+
+#[get("/search?<q>")]
+async fn search_html(q: Option<String>, state: State<'_, ArchiveState>) -> Template {
+    let query = q.unwrap_or_default();
+    let results = run_search(&state, &query, SEARCH_LIMIT).await;
+    let ctx = SearchContext {
+        query: &query,
+        results: &results,
+        parent: "layout",
+    };
+    Template::render("search", &ctx)
+}
+
+#[get("/api/search?<q>&<limit>")]
+async fn search_api(
+    q: String,
+    limit: Option<usize>,
+    state: State<'_, ArchiveState>,
+) -> Json<Vec<SearchResult>> {
+    let limit = limit.unwrap_or(SEARCH_LIMIT).min(SEARCH_LIMIT);
+    Json(run_search(&state, &q, limit).await)
+}
+
 #[derive(serde::Serialize)]
 struct IndexContext {
     parent: &'static str,
@@ -86,26 +551,145 @@ fn not_found(req: &Request<'_>) -> Template {
 
 struct ArchiveState {
     archive: RwLock<Archive>,
+    links: RwLock<LinkIndex>,
+    search: RwLock<SearchIndex>,
+    render: RenderSettings,
+    upload: UploadSettings,
+}
+
+/// The settings used when storing uploaded assets. The size of an upload is
+/// limited by the `file` and `data-form` limits in the Rocket config.
+#[derive(Clone, Copy)]
+struct UploadSettings {
+    sniff: bool,
+}
+impl UploadSettings {
+    /// Reads the settings from the environment. The type of an upload is
+    /// taken from its contents when it is a known type, rather than trusting
+    /// the type it was sent with, unless `ARCHIVE_SNIFF_MIME` is set to
+    /// `false` or `0`.
+    fn from_env() -> Self {
+        let sniff = !matches!(
+            env::var("ARCHIVE_SNIFF_MIME").as_deref(),
+            Ok("0") | Ok("false")
+        );
+        Self { sniff }
+    }
+}
+
+/// The settings used when rendering notes to HTML.
+#[derive(Clone, Copy)]
+struct RenderSettings {
+    math: mark::MathStyle,
+    typography: bool,
+}
+impl RenderSettings {
+    /// Reads the settings from the environment. Math is rendered by `KaTeX` in
+    /// the browser unless `ARCHIVE_MATH` is `mathml`, typographic
+    /// replacements are made if `ARCHIVE_TYPOGRAPHY` is set to `true` or `1`.
+    fn from_env() -> Self {
+        let math = match env::var("ARCHIVE_MATH") {
+            Ok(val) if val.eq_ignore_ascii_case("mathml") => mark::MathStyle::MathMl,
+            _ => mark::MathStyle::Source,
+        };
+        let typography = matches!(
+            env::var("ARCHIVE_TYPOGRAPHY").as_deref(),
+            Ok("1") | Ok("true")
+        );
+        Self { math, typography }
+    }
+
+    /// Returns the options for rendering a note, with note links resolved
+    /// against the notes in `links`.
+    fn options(self, links: &LinkIndex) -> mark::Options<'_> {
+        mark::Options {
+            resolver: Some(links),
+            math: self.math,
+            typography: self.typography,
+            header_ids: true,
+            ..mark::Options::default()
+        }
+    }
 }
 
 static SERVER_DEFAULT_ASSET_PATH: &str = "./data/assets";
 static SERVER_DEFAULT_DATA_PATH: &str = "./data/data";
+static SERVER_DEFAULT_INDEX_PATH: &str = "./data/search-index.json";
 
 #[launch]
-fn rocket() -> rocket::Rocket {
+async fn rocket() -> rocket::Rocket {
     let asset_path =
         env::var("ARCHIVE_ASSET_PATH").unwrap_or_else(|_| SERVER_DEFAULT_ASSET_PATH.to_string());
     let data_path =
         env::var("ARCHIVE_DATA_PATH").unwrap_or_else(|_| SERVER_DEFAULT_DATA_PATH.to_string());
+    let index_path =
+        env::var("ARCHIVE_INDEX_PATH").unwrap_or_else(|_| SERVER_DEFAULT_INDEX_PATH.to_string());
     let archive = Archive::new(&data_path, &asset_path);
+    let links = LinkIndex::new(&data_path);
+
+    // The index is saved once Rocket has set up logging. It can still be
+    // searched if it can't be saved.
+    let rocket = rocket::ignite();
+    let mut search = SearchIndex::new(&data_path, &index_path);
+    if let Err(err) = search.save().await {
+        log::error!("failed to save the search index to {}: {}", index_path, err);
+    }
 
-    rocket::ignite()
+    rocket
         .attach(Template::fairing())
         .register(catchers![not_found])
         .mount("/", StaticFiles::from(crate_relative!("public")))
-        .mount("/", routes![assets, index])
-        .mount("/", routes![note_plain, note_html])
+        .mount("/", routes![assets, upload_asset, index])
+        .mount("/", routes![note_plain, note_html, toggle_task])
+        .mount("/", routes![note_backlinks, note_ast, render_preview])
+        .mount("/", routes![notes, notes_dir, search_html, search_api])
+        .mount("/", routes![new_note, edit_note, create_note, update_note])
         .manage(ArchiveState {
             archive: RwLock::new(archive),
+            links: RwLock::new(links),
+            search: RwLock::new(search),
+            render: RenderSettings::from_env(),
+            upload: UploadSettings::from_env(),
         })
 }
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::Accept;
+    use rocket::local::asynchronous::Client;
+    use std::env;
+    use std::fs;
+
+    /// Returns the HTML page at `uri`.
+    async fn page(client: &Client, uri: &str) -> String {
+        client
+            .get(uri)
+            .header(Accept::HTML)
+            .dispatch()
+            .await
+            .into_string()
+            .await
+            .unwrap()
+    }
+
+    #[rocket::async_test]
+    async fn note_pages() {
+        let dir = env::temp_dir().join(format!("archive-test-{}", std::process::id()));
+        let data = dir.join("data");
+        fs::create_dir_all(&data).unwrap();
+        fs::write(data.join("todo.md"), "- [ ] Task\n").unwrap();
+        fs::create_dir_all(data.join("draft")).unwrap();
+        fs::write(data.join("draft/edit"), "Not the editor\n").unwrap();
+        env::set_var("ARCHIVE_DATA_PATH", &data);
+        env::set_var("ARCHIVE_ASSET_PATH", dir.join("assets"));
+        env::set_var("ARCHIVE_INDEX_PATH", dir.join("search-index.json"));
+
+        let client = Client::tracked(super::rocket().await).await.unwrap();
+        let todo = page(&client, "/note/todo.md").await;
+        let draft = page(&client, "/note/draft/edit").await;
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(todo.contains("<input type=\"checkbox\" data-line=\"1\""));
+        assert!(draft.contains("<p>Not the editor</p>"));
+    }
+}